@@ -0,0 +1,188 @@
+//! Throughput of the lexer on a large synthetic source made of random declarations and nested blocks.
+//!
+//! Run with `cargo bench --bench lexer`, throughputs are reported in MiB/s. To compare with an earlier revision of
+//! the lexer, such as f1c9775 before the DFA scanner, save a baseline from a worktree of that revision holding
+//! this bench and the current Cargo.toml, then compare against it from the current tree:
+//!
+//! ```text
+//! git worktree add ../previous f1c9775
+//! cp -r Cargo.toml benches ../previous/compiler/
+//! (cd ../previous/compiler && cargo bench --bench lexer -- --save-baseline previous)
+//! cp -r ../previous/compiler/target/criterion target/
+//! cargo bench --bench lexer -- --baseline previous
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use ecsl_compiler::lexer::Lexer;
+
+/// Minimum size of the generated source in bytes
+const SOURCE_LEN: usize = 4 << 20;
+
+/// Maximum nesting of the blocks of a generated system
+const MAX_DEPTH: usize = 3;
+
+const SYLLABLES: [&str; 12] = [
+    "ka", "ro", "mi", "te", "lu", "zan", "or", "vé", "shi", "ba", "dex", "um",
+];
+const TYPES: [&str; 6] = ["int", "float", "string", "bool", "duration", "[Item]"];
+const OPERATORS: [&str; 8] = ["+", "-", "*", "/", "==", "<=", "&&", "||"];
+const LITERALS: [&str; 10] = [
+    "12",
+    "-34l",
+    "12ul",
+    "23.35f",
+    "0x1F",
+    "1_000",
+    "1h20.5s",
+    "300ms",
+    "#FF8800",
+    "(1 -2; 3 4)",
+];
+
+/// Generates random sources, seeded so every run lexes the same source
+struct Generator {
+    rng: StdRng,
+    source: String,
+}
+
+impl Generator {
+    fn new() -> Generator {
+        return Generator {
+            rng: StdRng::seed_from_u64(0),
+            source: String::new(),
+        };
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        return items[self.rng.gen_range(0..items.len())];
+    }
+
+    fn name(&mut self, capitalised: bool) -> String {
+        let mut name = String::new();
+        for _ in 0..self.rng.gen_range(1..4) {
+            name.push_str(self.pick(&SYLLABLES));
+        }
+        if capitalised {
+            name[..1].make_ascii_uppercase();
+        }
+        return name;
+    }
+
+    fn operand(&mut self) -> String {
+        return match self.rng.gen_range(0..4) {
+            0 => self.pick(&LITERALS).to_string(),
+            1 => format!("{}.{}", self.name(false), self.name(false)),
+            2 => format!("{}[{}]", self.name(false), self.rng.gen_range(0..10)),
+            _ => self.name(false),
+        };
+    }
+
+    fn expression(&mut self) -> String {
+        let mut expression = self.operand();
+        for _ in 0..self.rng.gen_range(0..4) {
+            let operator = self.pick(&OPERATORS);
+            let operand = self.operand();
+            expression = format!("{} {} {}", expression, operator, operand);
+        }
+        return expression;
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.source.push_str("    ");
+        }
+        self.source.push_str(text);
+        self.source.push('\n');
+    }
+
+    fn statement(&mut self, depth: usize) {
+        let nested = depth < MAX_DEPTH;
+        match self.rng.gen_range(0..8) {
+            0 if nested => {
+                let condition = self.expression();
+                self.line(depth, &format!("if {}", condition));
+                self.block(depth + 1);
+                self.line(depth, "else");
+                self.block(depth + 1);
+            }
+            1 if nested => {
+                let condition = self.expression();
+                self.line(depth, &format!("while {}", condition));
+                self.block(depth + 1);
+            }
+            2 => {
+                let (name, value) = (self.name(false), self.operand());
+                self.line(depth, &format!("print \"{} = {{}}\" {}", name, value));
+            }
+            3 => {
+                let (comment, literal) = (self.name(false), self.pick(&LITERALS));
+                self.line(depth, &format!("# {} {}", comment, literal));
+            }
+            _ => {
+                let (target, value) = (self.operand(), self.expression());
+                self.line(depth, &format!("{} = {}", target, value));
+            }
+        }
+    }
+
+    fn block(&mut self, depth: usize) {
+        for _ in 0..self.rng.gen_range(1..5) {
+            self.statement(depth);
+        }
+    }
+
+    fn component(&mut self) {
+        let name = self.name(true);
+        self.line(0, &format!("component {}", name));
+        for _ in 0..self.rng.gen_range(1..6) {
+            let (field, ty) = (self.name(false), self.pick(&TYPES));
+            self.line(1, &format!("{}: {}", field, ty));
+        }
+    }
+
+    fn system(&mut self) {
+        let name = self.name(true);
+        self.line(0, &format!("system {}", name));
+        for _ in 0..self.rng.gen_range(1..4) {
+            let (access, component) =
+                (self.pick(&["reads", "mutates", "without"]), self.name(true));
+            self.line(1, &format!("{} {}", access, component));
+        }
+        self.line(1, "(entity) =>");
+        self.block(2);
+    }
+}
+
+/// Generate random components, systems and statements until the source is at least `SOURCE_LEN` bytes long
+fn generate_source() -> String {
+    let mut generator = Generator::new();
+    while generator.source.len() < SOURCE_LEN {
+        match generator.rng.gen_range(0..3) {
+            0 => generator.component(),
+            1 => generator.system(),
+            _ => generator.statement(0),
+        }
+        generator.source.push('\n');
+    }
+    return generator.source;
+}
+
+fn throughput(c: &mut Criterion) {
+    let source = generate_source();
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.sample_size(20);
+    group.bench_function("scan_str", |b| {
+        let mut lexer = Lexer::new();
+        b.iter(|| lexer.scan_str(black_box(&source)).unwrap().len());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
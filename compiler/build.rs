@@ -0,0 +1,118 @@
+//! Generates the keyword and operator variants of `TokenType` from token.yaml, which is shared with the VM and
+//! editor tooling. See `token.rs` for how the generated code is included.
+
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::{env, fs};
+
+#[path = "src/spec.rs"]
+mod spec;
+
+use spec::{variant_name, Section, Spec};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let spec_path = Path::new(&manifest_dir).join("../token.yaml");
+    println!("cargo:rerun-if-changed={}", spec_path.display());
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/spec.rs");
+
+    let source = fs::read_to_string(&spec_path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", spec_path.display(), e));
+    let spec: Spec = serde_yaml::from_str(&source)
+        .unwrap_or_else(|e| panic!("failed to parse {}: {}", spec_path.display(), e));
+    if let Err(e) = spec.validate() {
+        panic!("invalid {}: {}", spec_path.display(), e);
+    }
+
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("tokens.rs");
+    fs::write(&out_path, spec.generate()).unwrap();
+}
+
+impl Spec {
+    fn generate(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "// @generated by build.rs from token.yaml, do not edit"
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+
+        // the enum is declared through a macro so the variants which carry data can be declared in token.rs
+        writeln!(out, "macro_rules! define_token_type {{").unwrap();
+        writeln!(
+            out,
+            "    ($(#[$meta:meta])* $vis:vis enum $name:ident {{ $($variants:tt)* }}) => {{"
+        )
+        .unwrap();
+        writeln!(out, "        $(#[$meta])*").unwrap();
+        writeln!(out, "        $vis enum $name {{").unwrap();
+        for (constant, value) in self.entries() {
+            writeln!(out, "            /// `{}` ({})", value, constant).unwrap();
+            writeln!(out, "            {},", variant_name(constant)).unwrap();
+        }
+        writeln!(out, "            $($variants)*").unwrap();
+        writeln!(out, "        }}").unwrap();
+        writeln!(out, "    }};").unwrap();
+        writeln!(out, "}}").unwrap();
+
+        write_table(&mut out, "OPERATORS", "Operators", &self.operators).unwrap();
+        write_table(&mut out, "KEYWORDS", "Keywords", &self.keywords).unwrap();
+        write_table(
+            &mut out,
+            "CONTEXTUAL_KEYWORDS",
+            "Contextual keywords",
+            &self.contextual_keywords,
+        )
+        .unwrap();
+
+        writeln!(out).unwrap();
+        writeln!(out, "impl TokenType {{").unwrap();
+        writeln!(
+            out,
+            "    /// Returns the word of a contextual keyword, which is a valid identifier outside of its clause"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    pub fn contextual_keyword(&self) -> Option<&'static str> {{"
+        )
+        .unwrap();
+        writeln!(out, "        return match self {{").unwrap();
+        for (constant, value) in &self.contextual_keywords.0 {
+            writeln!(
+                out,
+                "            TokenType::{} => Some({:?}),",
+                variant_name(constant),
+                value
+            )
+            .unwrap();
+        }
+        writeln!(out, "            _ => None,").unwrap();
+        writeln!(out, "        }};").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+        return out;
+    }
+}
+
+fn write_table(out: &mut String, name: &str, title: &str, section: &Section) -> fmt::Result {
+    writeln!(out)?;
+    writeln!(out, "/// {} defined in token.yaml", title)?;
+    writeln!(
+        out,
+        "pub const {}: [(&str, TokenType); {}] = [",
+        name,
+        section.0.len()
+    )?;
+    for (constant, value) in &section.0 {
+        writeln!(
+            out,
+            "    ({:?}, TokenType::{}),",
+            value,
+            variant_name(constant)
+        )?;
+    }
+    return writeln!(out, "];");
+}
@@ -0,0 +1,367 @@
+//! Abstract syntax tree produced by the parser, every node carries the span of the source it was parsed from.
+
+use crate::token::{Color, Float, Integer, Span, TemplateSegment};
+
+/// Declarations of a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub declarations: Vec<Declaration>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub kind: DeclarationKind,
+    /// Text of the `##` or `///` comments preceding the declaration, one line per comment
+    pub doc: Option<String>,
+    /// Whether the declaration is visible to other files, see `expose`
+    pub exposed: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeclarationKind {
+    Record(RecordDecl),
+    Function(FunctionDecl),
+    System(SystemDecl),
+    Handler(HandlerDecl),
+    Variable(VariableDecl),
+    Statement(Statement),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+/// `component Health`, `state Counter` or `structure Person` followed by an indented list of fields such as
+/// `max: int`, a component without fields is a marker
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordDecl {
+    pub kind: RecordKind,
+    pub name: Identifier,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Component,
+    State,
+    Structure,
+    Event,
+}
+
+/// `name: Type`, `name Type` or `name ?: Type`, optionally followed by `optional` and `default=value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: Identifier,
+    pub ty: TypeExpr,
+    pub optional: bool,
+    pub default: Option<Expr>,
+    /// Doc comments preceding the field
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// `function Name` followed by a lambda, on the same line or on an indented line
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Identifier,
+    pub lambda: Lambda,
+}
+
+/// `handle Event` or `hook Event` followed by the lambda run when the event is emitted, either on the same line or
+/// on an indented line following `filter` clauses
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerDecl {
+    pub kind: HandlerKind,
+    pub event: Vec<Identifier>,
+    /// Conditions of `filter` clauses
+    pub filters: Vec<Expr>,
+    pub lambda: Lambda,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandlerKind {
+    /// Handlers of an event run in parallel
+    Handle,
+    /// Hooks of an event run in sequence and may change the event
+    Hook,
+}
+
+/// `system Name` followed by indented clauses, one per line, and by the lambda run by the system
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemDecl {
+    pub name: Identifier,
+    /// `reads`, `mutates`, `without`, `on` and `foreach` clauses in declaration order
+    pub accesses: Vec<Access>,
+    /// Conditions of `filter` clauses
+    pub filters: Vec<Expr>,
+    /// `after` and `before` clauses
+    pub ordering: Vec<Ordering>,
+    /// `depends` clauses
+    pub dependencies: Vec<Dependency>,
+    pub lambda: Lambda,
+}
+
+/// Component, event or state accessed by a system, such as `mutates event Message`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Access {
+    pub mode: AccessMode,
+    pub target: AccessTarget,
+    pub path: Vec<Identifier>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    /// `reads`
+    Read,
+    /// `mutates`
+    Write,
+    /// `without`, entities having the component are skipped
+    Without,
+    /// `on`, the system runs when the event is emitted
+    On,
+    /// `foreach`, the system runs once per entity or state
+    Foreach,
+}
+
+/// Kind of what is accessed, components are accessed unless the name is preceded by `event` or `state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessTarget {
+    Component,
+    Event,
+    State,
+}
+
+/// `after Incrementer` or `before Printer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ordering {
+    pub constraint: OrderConstraint,
+    pub system: Vec<Identifier>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderConstraint {
+    After,
+    Before,
+}
+
+/// `depends db [Postgres.Connection]`, a resource injected into the system under the given name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: Identifier,
+    pub ty: TypeExpr,
+    pub span: Span,
+}
+
+/// `name: value`, `name (mutable): value`, `name (Type): value` or `const name = value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableDecl {
+    pub name: Identifier,
+    pub constant: bool,
+    pub mutable: bool,
+    pub ty: Option<TypeExpr>,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
+    Expression(Expr),
+    Assignment {
+        target: Expr,
+        operator: AssignOperator,
+        value: Expr,
+    },
+    Return(Option<Expr>),
+    /// `use IO`
+    Use(Vec<Identifier>),
+    /// `if condition` followed by a block, an `else if` is an `else` block holding a single `if` statement
+    If {
+        condition: Expr,
+        then: Block,
+        otherwise: Option<Block>,
+    },
+    While {
+        condition: Expr,
+        body: Block,
+    },
+    /// `emit Event`, where the event may be followed by the arguments of a call, by `to state` to only emit it
+    /// to a single state, and by `with priority value`
+    Emit {
+        event: Expr,
+        target: Option<Expr>,
+        priority: Option<Box<Expr>>,
+    },
+}
+
+/// Statements of an indented block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOperator {
+    /// `=`
+    Assign,
+    /// `+=`
+    Add,
+    /// `-=`
+    Subtract,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Identifier(String),
+    Literal(Literal),
+    SelfRef,
+    Binary {
+        operator: BinaryOperator,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Unary {
+        operator: UnaryOperator,
+        operand: Box<Expr>,
+    },
+    /// `object.member`
+    Member {
+        object: Box<Expr>,
+        member: Identifier,
+    },
+    /// `callee(arguments...)`, or `callee arguments...` separated by whitespace for a call statement such as
+    /// `print "a = {}" a`
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+    },
+    /// `object[index]`, which is also the component access `entity[Health]`
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `entity<Health>`
+    Component {
+        entity: Box<Expr>,
+        component: TypeExpr,
+    },
+    /// `(a, b)`, parentheses around a single expression only change its span
+    Tuple(Vec<Expr>),
+    /// `[a, b]`
+    Array(Vec<Expr>),
+    /// `(1 2; 3 4)` matrix, or `v(1 2)` column vector
+    Matrix {
+        rows: Vec<Vec<Expr>>,
+        column: bool,
+    },
+    Lambda(Lambda),
+    /// `new Counter`
+    New(Box<Expr>),
+    /// `Message { message: 'hello!', sender: 'yonder' }`
+    Record {
+        ty: Box<Expr>,
+        fields: Vec<(Identifier, Expr)>,
+    },
+}
+
+/// Binary operators, see `parser::binary_operator` for their precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    /// `-`
+    Negate,
+    /// `!`
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(Integer),
+    Float(Float),
+    Duration(u64),
+    String(String),
+    Char(char),
+    Bool(bool),
+    Color(Color),
+    /// Interpolated string, the tokens of its placeholders are left for later passes
+    Template(Vec<TemplateSegment>),
+}
+
+/// `(parameters...) => body`, or `parameter => body` with a single untyped parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lambda {
+    pub parameters: Vec<Parameter>,
+    /// Type following the `=>` of a lambda whose body is an indented block
+    pub return_type: Option<TypeExpr>,
+    pub body: LambdaBody,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LambdaBody {
+    /// Expression on the same line as the `=>`
+    Expression(Box<Expr>),
+    Block(Block),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: Identifier,
+    pub ty: Option<TypeExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    /// `string`, `HTTP.Header` or `Entity<HTTP.Header>`
+    Named {
+        path: Vec<Identifier>,
+        arguments: Vec<TypeExpr>,
+    },
+    /// `[T]`
+    Array(Box<TypeExpr>),
+    /// `[K, V]`
+    Map(Box<TypeExpr>, Box<TypeExpr>),
+    /// `{T}`
+    Set(Box<TypeExpr>),
+    /// `(T1, T2)`
+    Tuple(Vec<TypeExpr>),
+}
@@ -44,7 +44,7 @@ impl<T> Arena<T> {
         self.len += 1;
         match self.empty.pop() {
             Some(index) => {
-                let version = self.versions[index] * -1 + 1;
+                let version = -self.versions[index] + 1;
                 self.data[index] = Some(value);
                 return ID { index, version };
             }
@@ -73,13 +73,17 @@ impl<T> Arena<T> {
     pub fn len(&self) -> usize {
         self.len
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 }
 
 impl<T> Index<ID> for Arena<T> {
     type Output = T;
 
     fn index(&self, index: ID) -> &Self::Output {
-        &self.find(index).unwrap()
+        self.find(index).unwrap()
     }
 }
 
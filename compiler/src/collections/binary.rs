@@ -3,7 +3,7 @@
  */
 use std::ops::{Index, IndexMut};
 
-struct BinaryVec<T>(Vec<T>)
+pub struct BinaryVec<T>(Vec<T>)
 where
     T: PartialOrd;
 
@@ -19,7 +19,7 @@ where
         if self.0.is_empty() {
             self.0.push(value);
         } else {
-            let (mut start, mut end) = (0 as usize, self.len() - 1);
+            let (mut start, mut end) = (0_usize, self.len() - 1);
             loop {
                 let range = end - start;
                 if range <= 1 {
@@ -50,6 +50,10 @@ where
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<T> Index<usize> for BinaryVec<T>
@@ -106,7 +110,7 @@ where
 
     fn into_iter(self) -> Self::IntoIter {
         BinaryVecIterator {
-            vec: &self,
+            vec: self,
             index: 0,
         }
     }
@@ -120,7 +124,6 @@ fn mid(start: usize, end: usize) -> usize {
 mod test {
     use crate::collections::binary::BinaryVec;
     use rand;
-    use std;
 
     #[test]
     fn push() {
@@ -130,7 +133,7 @@ mod test {
             v.push(rand::random::<i32>().abs() % 10);
         }
         assert_eq!(v.len(), n);
-        let mut last = std::i32::MIN;
+        let mut last = i32::MIN;
         for item in &v {
             if *item < last {
                 panic!("wrong order")
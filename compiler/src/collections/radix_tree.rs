@@ -1,4 +1,4 @@
-use std::{cmp::min, fmt, mem, vec};
+use std::{cmp::min, fmt, vec};
 
 use super::arena::{Arena, ID};
 
@@ -26,6 +26,30 @@ impl<T> RadixTree<T> {
         self.insert_to_node(root_id, key, value);
     }
 
+    /// Find the value of the longest key which is a prefix of the given input, returning the length of the key
+    /// along with its value.
+    ///
+    /// For example with the keys "=" and "=>", the input "=>x" resolves to "=>" while "=x" resolves to "=".
+    pub fn find_longest_prefix(&self, input: &[u8]) -> Option<(usize, &T)> {
+        let mut node_id = self.root;
+        let mut index = 0;
+        let mut longest = None;
+        'node_iterator: loop {
+            for child_id in &self.arena[node_id].children {
+                let child = &self.arena[*child_id];
+                if input[index..].starts_with(&child.key) {
+                    index += child.key.len();
+                    if let Some(value) = &child.value {
+                        longest = Some((index, value));
+                    }
+                    node_id = *child_id;
+                    continue 'node_iterator;
+                }
+            }
+            return longest;
+        }
+    }
+
     // pub fn find(&self, key: Vec<u8>) -> Option<T> {
     //     let mut current_node_id = self.root;
     //     let mut index = 0;
@@ -67,7 +91,7 @@ impl<T> RadixTree<T> {
     //     self.current_match = matched_node_id;
     // }
 
-    /// find a node by key
+    // find a node by key
     // fn find_node(&self, key: Vec<u8>) -> Option<&T> {
 
     // }
@@ -77,8 +101,9 @@ impl<T> RadixTree<T> {
         for child_id_ref in &node.children {
             let child_id = *child_id_ref;
             let child = &self.arena[child_id];
-            // input and child matched perfectly, insertion is no-op
+            // input and child matched perfectly, replace the value of the child
             if child.key == key {
+                self.arena[child_id].value = Some(value);
                 return;
             }
 
@@ -122,7 +147,7 @@ impl<T> RadixTree<T> {
 
         let intermediate = Exact {
             children,
-            value: mem::replace(&mut node.value, None),
+            value: node.value.take(),
             key: rest.to_vec(),
         };
         let intermediate_id = self.arena.push(intermediate);
@@ -195,7 +220,7 @@ where
     T: std::fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\n")?;
+        writeln!(f)?;
         for node in self {
             if node.1 == 1 {
                 let str = std::str::from_utf8(&node.0.key).unwrap();
@@ -212,7 +237,7 @@ where
                 Some(value) => write!(f, " -> {:?}", value)?,
             }
 
-            write!(f, "\n")?;
+            writeln!(f)?;
 
             // └ ├ ─
         }
@@ -231,7 +256,7 @@ pub struct Predictor<'a, T> {
 }
 
 impl<'a, T> Predictor<'a, T> {
-    pub fn new(tree: &RadixTree<T>) -> Predictor<T> {
+    pub fn new(tree: &RadixTree<T>) -> Predictor<'_, T> {
         Predictor {
             tree,
             current_match: tree.root,
@@ -243,8 +268,8 @@ impl<'a, T> Predictor<'a, T> {
         }
     }
 
-    pub fn add(&mut self, key: &Vec<u8>) {
-        self.key.append(&mut key.clone());
+    pub fn add(&mut self, key: &[u8]) {
+        self.key.extend_from_slice(key);
         self.update();
     }
 
@@ -272,7 +297,7 @@ impl<'a, T> Predictor<'a, T> {
 
     pub fn get_exact(&self) -> Option<&T> {
         if self.exact {
-            return Some(&self.tree.arena[self.current_match].value.as_ref().unwrap());
+            return Some(self.tree.arena[self.current_match].value.as_ref().unwrap());
         }
         return None;
     }
@@ -287,7 +312,7 @@ impl<'a, T> Predictor<'a, T> {
     }
 
     pub fn is_empty(&self) -> bool {
-        return self.key.len() == 0;
+        return self.key.is_empty();
     }
 
     pub fn value(&self) -> &Vec<u8> {
@@ -305,7 +330,7 @@ impl<'a, T> Predictor<'a, T> {
             most_common_node_id = None;
 
             // exact match with the current node
-            if key.len() == 0 {
+            if key.is_empty() {
                 self.current_match = matched_node_id;
                 if current_node.value.is_some() {
                     self.exact = true;
@@ -359,8 +384,7 @@ fn find_common_prefix_count(node_base_str: &[u8], str2: &[u8]) -> usize {
 
 #[cfg(test)]
 mod test {
-    use crate::collections::radix_tree::{self, RadixTree};
-    use rand;
+    use crate::collections::radix_tree::RadixTree;
     use std;
 
     use super::Predictor;
@@ -384,25 +408,40 @@ mod test {
         }
 
         let mut p = Predictor::new(&t);
-        p.add(&"h".as_bytes().to_vec());
+        p.add("h".as_bytes());
         expect_prediction(&p, Some("ham"));
-        p.add(&"a".as_bytes().to_vec());
+        p.add("a".as_bytes());
         expect_prediction(&p, Some("ham"));
-        p.add(&"m".as_bytes().to_vec());
+        p.add("m".as_bytes());
         expect_prediction(&p, None);
-        p.add(&"b".as_bytes().to_vec());
+        p.add("b".as_bytes());
         expect_prediction(&p, Some("hamburg"));
-        p.add(&"u".as_bytes().to_vec());
+        p.add("u".as_bytes());
         expect_prediction(&p, Some("hamburg"));
-        p.add(&"r".as_bytes().to_vec());
+        p.add("r".as_bytes());
         expect_prediction(&p, Some("hamburg"));
-        p.add(&"g".as_bytes().to_vec());
+        p.add("g".as_bytes());
         expect_prediction(&p, None);
-        p.add(&"e".as_bytes().to_vec());
+        p.add("e".as_bytes());
         expect_prediction(&p, Some("hamburger"));
-        p.add(&"r".as_bytes().to_vec());
+        p.add("r".as_bytes());
         expect_prediction(&p, None);
-        p.add(&"s".as_bytes().to_vec());
+        p.add("s".as_bytes());
         expect_prediction(&p, None);
     }
+
+    #[test]
+    fn find_longest_prefix() {
+        let mut t: RadixTree<i32> = RadixTree::new();
+        t.insert("=>".as_bytes().to_vec(), 1);
+        t.insert("==".as_bytes().to_vec(), 2);
+        t.insert("=".as_bytes().to_vec(), 3);
+        t.insert("?:".as_bytes().to_vec(), 4);
+        assert_eq!(t.find_longest_prefix("=> x".as_bytes()), Some((2, &1)));
+        assert_eq!(t.find_longest_prefix("==".as_bytes()), Some((2, &2)));
+        assert_eq!(t.find_longest_prefix("=x".as_bytes()), Some((1, &3)));
+        assert_eq!(t.find_longest_prefix("?:".as_bytes()), Some((2, &4)));
+        assert_eq!(t.find_longest_prefix("?".as_bytes()), None);
+        assert_eq!(t.find_longest_prefix("x".as_bytes()), None);
+    }
 }
@@ -1,52 +1,275 @@
-// use std::fmt;
-
-// /**
-//  * dictionary.rs
-//  *
-//  * Dictionary is a struct that contains all keyword, operator, and pattern definitions.  
-//  */
-
-// pub struct Error {
-//     record: Record,
-// }
-
-// impl fmt::Display for Error {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         write!(f, "dictionary error for record: {}", self.record)
-//     }
-// }
-
-// struct Dictionary {
-//     records: Vec<Record>,
-// }
-
-// impl Dictionary {
-//     pub fn new() -> Dictionary {
-//         return Dictionary {
-//             records: Vec::new(),
-//         };
-//     }
-
-//     pub fn add_record(&mut self, record: Record) -> Result<(), Error> {
-//         Ok(())
-//     }
-// }
-
-// /// Definition of a token inside a dictionary
-// enum Record {
-//     Exact(i32, String),
-//     Pattern(i32, String),
-// }
-
-// impl fmt::Display for Record {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         match &self {
-//             Self::Exact(priority, string) => {
-//                 write!(f, "Exact   ({}): {}", priority, string)
-//             }
-//             Self::Pattern(priority, string) => {
-//                 write!(f, "Pattern ({}): {}", priority, string)
-//             }
-//         }
-//     }
-// }
+//! Dictionary is a struct that contains all keyword, operator, and pattern definitions, as prioritised records.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::fsm::{Dfa, Nfa};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    record: Record,
+    kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// An existing record of the same priority matches some of the same words
+    Conflict(Record),
+    InvalidPattern(String),
+}
+
+impl Error {
+    pub fn record(&self) -> &Record {
+        return &self.record;
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        return &self.kind;
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dictionary error for record: {}", self.record)?;
+        match &self.kind {
+            ErrorKind::Conflict(record) => write!(f, ", ambiguous with {}", record),
+            ErrorKind::InvalidPattern(error) => write!(f, ", {}", error),
+        }
+    }
+}
+
+/// Records along with the value a word matching them resolves to. A word matching several records resolves to
+/// the record of the highest priority, records of the same priority may not match the same word
+#[derive(Clone)]
+pub struct Dictionary<T> {
+    records: Vec<(Record, T)>,
+    /// Indices of the exact records by their text
+    exact: HashMap<String, Vec<usize>>,
+    /// Compiled pattern of each pattern record, by index of the record
+    patterns: HashMap<usize, Pattern>,
+}
+
+#[derive(Clone)]
+struct Pattern {
+    /// Regex matching whole words only
+    regex: Regex,
+    /// Automaton accepting the words of the pattern, or a superset of them when it is not exact
+    dfa: Dfa<()>,
+    exact: bool,
+}
+
+impl<T> Dictionary<T> {
+    pub fn new() -> Dictionary<T> {
+        return Dictionary {
+            records: Vec::new(),
+            exact: HashMap::new(),
+            patterns: HashMap::new(),
+        };
+    }
+
+    /// Add a record, failing if its pattern is invalid or if it is ambiguous with an existing record of the
+    /// same priority. Patterns match whole words, and two patterns conflict when some word could match both
+    pub fn add_record(&mut self, record: Record, value: T) -> Result<(), Error> {
+        let error = |kind| Error {
+            record: record.clone(),
+            kind,
+        };
+        let pattern = match &record {
+            Record::Exact(..) => None,
+            Record::Pattern(_, pattern) => {
+                let invalid =
+                    |e: &dyn fmt::Display| error(ErrorKind::InvalidPattern(e.to_string()));
+                let regex = Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| invalid(&e))?;
+                let mut nfa = Nfa::new();
+                let exact = nfa.add_pattern(pattern, 0, ()).map_err(|e| invalid(&e))?;
+                Some(Pattern {
+                    regex,
+                    dfa: Dfa::new(&nfa),
+                    exact,
+                })
+            }
+        };
+        if let Some(existing) = self.conflict(&record, pattern.as_ref()) {
+            return Err(error(ErrorKind::Conflict(existing.clone())));
+        }
+
+        let index = self.records.len();
+        match pattern {
+            Some(pattern) => {
+                self.patterns.insert(index, pattern);
+            }
+            None => {
+                let text = record.text().to_string();
+                self.exact.entry(text).or_default().push(index);
+            }
+        }
+        self.records.push((record, value));
+        return Ok(());
+    }
+
+    /// Returns an existing record of the same priority as a new record, which matches some of the same words
+    fn conflict(&self, record: &Record, pattern: Option<&Pattern>) -> Option<&Record> {
+        for (i, (existing, _)) in self.records.iter().enumerate() {
+            if existing.priority() != record.priority() {
+                continue;
+            }
+            let ambiguous = match (existing, pattern) {
+                (Record::Exact(_, text), None) => text == record.text(),
+                (Record::Exact(_, text), Some(pattern)) => pattern.regex.is_match(text),
+                (Record::Pattern(..), None) => self.patterns[&i].regex.is_match(record.text()),
+                (Record::Pattern(..), Some(pattern)) => {
+                    self.patterns[&i].dfa.intersects(&pattern.dfa)
+                }
+            };
+            if ambiguous {
+                return Some(existing);
+            }
+        }
+        return None;
+    }
+
+    /// Returns the value of the record of the highest priority matching a whole word
+    pub fn get(&self, word: &str) -> Option<&T> {
+        let exact = self.exact.get(word).into_iter().flatten();
+        let patterns = self
+            .patterns
+            .iter()
+            .filter(|(_, pattern)| pattern.regex.is_match(word))
+            .map(|(i, _)| i);
+        let (_, value) = exact
+            .chain(patterns)
+            .map(|i| &self.records[*i])
+            .max_by_key(|(record, _)| record.priority())?;
+        return Some(value);
+    }
+
+    /// Returns the records in the order they were added, a record is identified by its index in this order
+    pub fn records(&self) -> impl Iterator<Item = &(Record, T)> {
+        return self.records.iter();
+    }
+
+    pub fn record(&self, index: usize) -> &(Record, T) {
+        return &self.records[index];
+    }
+
+    /// Returns true if a record matches exactly the words accepted by `Nfa::add_literal` or `Nfa::add_pattern`, in
+    /// which case `is_match` does not need to be checked on the words accepted by the automaton. The automaton
+    /// only tells ASCII characters apart, see `fsm::symbol`, so exact records with other characters are not exact
+    pub fn is_exact(&self, index: usize) -> bool {
+        return match &self.records[index].0 {
+            Record::Exact(_, text) => text.is_ascii(),
+            Record::Pattern(..) => self.patterns[&index].exact,
+        };
+    }
+
+    /// Returns true if a record matches a whole word
+    pub fn is_match(&self, index: usize, word: &str) -> bool {
+        return match &self.records[index].0 {
+            Record::Exact(_, text) => text == word,
+            Record::Pattern(..) => self.patterns[&index].regex.is_match(word),
+        };
+    }
+}
+
+/// Definition of a token inside a dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    Exact(i32, String),
+    Pattern(i32, String),
+}
+
+impl Record {
+    pub fn priority(&self) -> i32 {
+        return match self {
+            Record::Exact(priority, _) | Record::Pattern(priority, _) => *priority,
+        };
+    }
+
+    /// Returns the text of an exact record or the pattern of a pattern record
+    pub fn text(&self) -> &str {
+        return match self {
+            Record::Exact(_, text) | Record::Pattern(_, text) => text,
+        };
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Self::Exact(priority, string) => {
+                write!(f, "Exact   ({}): {}", priority, string)
+            }
+            Self::Pattern(priority, string) => {
+                write!(f, "Pattern ({}): {}", priority, string)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dictionary, ErrorKind, Record};
+
+    fn exact(priority: i32, text: &str) -> Record {
+        return Record::Exact(priority, text.to_string());
+    }
+
+    fn pattern(priority: i32, pattern: &str) -> Record {
+        return Record::Pattern(priority, pattern.to_string());
+    }
+
+    #[test]
+    fn priorities() {
+        let mut dictionary = Dictionary::new();
+        dictionary
+            .add_record(pattern(0, "[a-z]+"), "identifier")
+            .unwrap();
+        dictionary.add_record(exact(1, "for"), "keyword").unwrap();
+        dictionary
+            .add_record(pattern(2, "[a-z]+_t"), "type")
+            .unwrap();
+        assert_eq!(dictionary.get("for"), Some(&"keyword"));
+        assert_eq!(dictionary.get("fort"), Some(&"identifier"));
+        assert_eq!(dictionary.get("int_t"), Some(&"type"));
+        assert_eq!(dictionary.get("FOR"), None);
+        assert!(dictionary.is_exact(1) && dictionary.is_match(2, "i_t"));
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut dictionary = Dictionary::new();
+        dictionary.add_record(exact(1, "for"), ()).unwrap();
+        dictionary.add_record(pattern(0, "[a-z]+"), ()).unwrap();
+        let conflict = |dictionary: &mut Dictionary<()>, record| {
+            return dictionary
+                .add_record(record, ())
+                .unwrap_err()
+                .kind()
+                .clone();
+        };
+        assert_eq!(
+            conflict(&mut dictionary, exact(1, "for")),
+            ErrorKind::Conflict(exact(1, "for"))
+        );
+        assert_eq!(
+            conflict(&mut dictionary, pattern(1, "f[aeiou]r")),
+            ErrorKind::Conflict(exact(1, "for"))
+        );
+        assert_eq!(
+            conflict(&mut dictionary, pattern(0, "[a-c]*x")),
+            ErrorKind::Conflict(pattern(0, "[a-z]+"))
+        );
+        assert!(matches!(
+            conflict(&mut dictionary, pattern(0, "[a-")),
+            ErrorKind::InvalidPattern(_)
+        ));
+
+        // disjoint patterns and records of another priority are fine
+        dictionary.add_record(pattern(0, "[0-9]+"), ()).unwrap();
+        dictionary.add_record(pattern(1, "f[aeiu]r"), ()).unwrap();
+        dictionary.add_record(exact(2, "for"), ()).unwrap();
+        assert_eq!(dictionary.records().count(), 5);
+    }
+}
@@ -0,0 +1,554 @@
+//! Finite state machines recognising lexemes. A non-deterministic automaton is built from the token definitions
+//! and compiled into a deterministic automaton, whose transitions are a table indexed by state and input class.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use regex_syntax::hir::{self, Anchor, Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind};
+use regex_syntax::hir::{RepetitionKind, RepetitionRange};
+use unicode_xid::UnicodeXID;
+
+/// Number of input symbols, every ASCII character is a symbol of its own while other characters are grouped
+/// by their identifier properties
+pub const SYMBOLS: usize = 131;
+
+/// Symbol of non-ASCII characters which may start an identifier
+pub const XID_START: usize = 128;
+/// Symbol of non-ASCII characters which may only continue an identifier
+pub const XID_CONTINUE: usize = 129;
+/// Symbol of any other non-ASCII character
+pub const OTHER: usize = 130;
+
+/// Returns the input symbol of a character
+pub fn symbol(c: char) -> usize {
+    if c.is_ascii() {
+        return c as usize;
+    }
+    if c.is_xid_start() {
+        return XID_START;
+    }
+    if c.is_xid_continue() {
+        return XID_CONTINUE;
+    }
+    return OTHER;
+}
+
+/// Set of input symbols labelling a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolSet([u64; 3]);
+
+impl SymbolSet {
+    pub fn new() -> SymbolSet {
+        return SymbolSet([0; 3]);
+    }
+
+    /// Set of the symbols matching a predicate
+    pub fn from_fn(f: impl Fn(usize) -> bool) -> SymbolSet {
+        let mut set = SymbolSet::new();
+        for symbol in (0..SYMBOLS).filter(|s| f(*s)) {
+            set.insert(symbol);
+        }
+        return set;
+    }
+
+    /// Set of the symbols of the given characters
+    pub fn of(chars: &str) -> SymbolSet {
+        let mut set = SymbolSet::new();
+        for c in chars.chars() {
+            set.insert(symbol(c));
+        }
+        return set;
+    }
+
+    pub fn insert(&mut self, symbol: usize) {
+        self.0[symbol / 64] |= 1 << (symbol % 64);
+    }
+
+    pub fn contains(&self, symbol: usize) -> bool {
+        return self.0[symbol / 64] & (1 << (symbol % 64)) != 0;
+    }
+
+    /// Returns the symbols of the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        return self.0.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                return Some(i * 64 + bit);
+            })
+        });
+    }
+
+    pub fn union(&self, other: &SymbolSet) -> SymbolSet {
+        return SymbolSet([
+            self.0[0] | other.0[0],
+            self.0[1] | other.0[1],
+            self.0[2] | other.0[2],
+        ]);
+    }
+}
+
+/// Returns the symbols of the characters of a class, along with whether the symbols stand for exactly these
+/// characters. Non-ASCII characters are only told apart by their identifier properties, so a class covering part
+/// of the characters of a symbol is widened to all of them
+fn class_symbols(class: &ClassUnicode) -> (SymbolSet, bool) {
+    static CATEGORIES: OnceLock<[ClassUnicode; 3]> = OnceLock::new();
+    let categories = CATEGORIES.get_or_init(|| {
+        let property =
+            |name: &str| match regex_syntax::Parser::new().parse(name).unwrap().into_kind() {
+                HirKind::Class(Class::Unicode(class)) => class,
+                _ => unreachable!(),
+            };
+        let ascii = ClassUnicode::new([ClassUnicodeRange::new('\0', '\x7F')]);
+        let mut start = property(r"\p{XID_Start}");
+        start.difference(&ascii);
+        let mut other = property(r"\p{XID_Continue}");
+        other.union(&ascii);
+        let mut continues = other.clone();
+        continues.difference(&start);
+        continues.difference(&ascii);
+        other.negate();
+        return [start, continues, other];
+    });
+
+    let mut symbols = SymbolSet::new();
+    let mut exact = true;
+    for range in class.ranges() {
+        for c in range.start()..=range.end().min('\x7F') {
+            symbols.insert(c as usize);
+        }
+    }
+    for (symbol, category) in [XID_START, XID_CONTINUE, OTHER].into_iter().zip(categories) {
+        let mut covered = category.clone();
+        covered.intersect(class);
+        if covered.ranges().is_empty() {
+            continue;
+        }
+        symbols.insert(symbol);
+        exact &= covered == *category;
+    }
+    return (symbols, exact);
+}
+
+/// Non-deterministic automaton with epsilon transitions, its start state is `Nfa::START`. A state accepts with a
+/// priority, the highest priority wins when the same input is accepted by several states
+pub struct Nfa<A> {
+    states: Vec<NfaState<A>>,
+}
+
+struct NfaState<A> {
+    transitions: Vec<(SymbolSet, usize)>,
+    epsilon: Vec<usize>,
+    accept: Option<(i32, A)>,
+}
+
+impl<A: Clone> Nfa<A> {
+    pub const START: usize = 0;
+
+    pub fn new() -> Nfa<A> {
+        let mut nfa = Nfa { states: Vec::new() };
+        nfa.add_state();
+        return nfa;
+    }
+
+    pub fn add_state(&mut self) -> usize {
+        self.states.push(NfaState {
+            transitions: Vec::new(),
+            epsilon: Vec::new(),
+            accept: None,
+        });
+        return self.states.len() - 1;
+    }
+
+    pub fn add_transition(&mut self, from: usize, symbols: SymbolSet, to: usize) {
+        self.states[from].transitions.push((symbols, to));
+    }
+
+    pub fn add_epsilon(&mut self, from: usize, to: usize) {
+        self.states[from].epsilon.push(to);
+    }
+
+    pub fn set_accept(&mut self, state: usize, priority: i32, value: A) {
+        self.states[state].accept = Some((priority, value));
+    }
+
+    /// Add a path from the start state accepting exactly the given text
+    pub fn add_literal(&mut self, text: &str, priority: i32, value: A) {
+        let mut state = Nfa::<A>::START;
+        for c in text.chars() {
+            let next = self.add_state();
+            self.add_transition(state, SymbolSet::of(&c.to_string()), next);
+            state = next;
+        }
+        self.set_accept(state, priority, value);
+    }
+
+    /// Add a path from the start state accepting the whole words matched by a regular expression, returning whether
+    /// exactly these words are accepted, see `class_symbols`, or the error message of an invalid pattern. Leading
+    /// `^` and trailing `$` anchors are implied, other assertions are ignored
+    pub fn add_pattern(&mut self, pattern: &str, priority: i32, value: A) -> Result<bool, String> {
+        let hir = regex_syntax::Parser::new()
+            .parse(pattern)
+            .map_err(|e| e.to_string())?;
+        let mut parts: Vec<&Hir> = match hir.kind() {
+            HirKind::Concat(parts) => parts.iter().collect(),
+            _ => vec![&hir],
+        };
+        if matches!(
+            parts.first().map(|p| p.kind()),
+            Some(HirKind::Anchor(Anchor::StartText))
+        ) {
+            parts.remove(0);
+        }
+        if matches!(
+            parts.last().map(|p| p.kind()),
+            Some(HirKind::Anchor(Anchor::EndText))
+        ) {
+            parts.pop();
+        }
+
+        let start = self.add_state();
+        self.add_epsilon(Nfa::<A>::START, start);
+        let mut exact = true;
+        let mut state = start;
+        for part in parts {
+            state = self.add_hir(part, state, &mut exact);
+        }
+        self.set_accept(state, priority, value);
+        return Ok(exact);
+    }
+
+    /// Add the states matching an expression after `from`, returning the state reached at the end of a match
+    fn add_hir(&mut self, hir: &Hir, from: usize, exact: &mut bool) -> usize {
+        let symbols = match hir.kind() {
+            HirKind::Empty => return from,
+            HirKind::Anchor(_) | HirKind::WordBoundary(_) => {
+                *exact = false;
+                return from;
+            }
+            HirKind::Group(group) => return self.add_hir(&group.hir, from, exact),
+            HirKind::Concat(parts) => {
+                return parts
+                    .iter()
+                    .fold(from, |state, part| self.add_hir(part, state, exact));
+            }
+            HirKind::Alternation(branches) => {
+                let end = self.add_state();
+                for branch in branches {
+                    let state = self.add_state();
+                    self.add_epsilon(from, state);
+                    let state = self.add_hir(branch, state, exact);
+                    self.add_epsilon(state, end);
+                }
+                return end;
+            }
+            HirKind::Repetition(repetition) => {
+                let (min, max) = match &repetition.kind {
+                    RepetitionKind::ZeroOrOne => (0, Some(1)),
+                    RepetitionKind::ZeroOrMore => (0, None),
+                    RepetitionKind::OneOrMore => (1, None),
+                    RepetitionKind::Range(RepetitionRange::Exactly(n)) => (*n, Some(*n)),
+                    RepetitionKind::Range(RepetitionRange::AtLeast(n)) => (*n, None),
+                    RepetitionKind::Range(RepetitionRange::Bounded(n, m)) => (*n, Some(*m)),
+                };
+                let mut state = from;
+                for _ in 0..min {
+                    state = self.add_hir(&repetition.hir, state, exact);
+                }
+                let end = self.add_state();
+                self.add_epsilon(state, end);
+                match max {
+                    Some(max) => {
+                        for _ in min..max {
+                            state = self.add_hir(&repetition.hir, state, exact);
+                            self.add_epsilon(state, end);
+                        }
+                    }
+                    None => {
+                        let repeated = self.add_hir(&repetition.hir, end, exact);
+                        self.add_epsilon(repeated, end);
+                    }
+                }
+                return end;
+            }
+            HirKind::Literal(hir::Literal::Unicode(c)) => {
+                *exact &= c.is_ascii();
+                SymbolSet::of(&c.to_string())
+            }
+            HirKind::Class(Class::Unicode(class)) => {
+                let (symbols, class_exact) = class_symbols(class);
+                *exact &= class_exact;
+                symbols
+            }
+            // bytes only match ASCII characters as a whole
+            HirKind::Literal(hir::Literal::Byte(b)) => {
+                *exact &= b.is_ascii();
+                SymbolSet::from_fn(|s| s == *b as usize)
+            }
+            HirKind::Class(Class::Bytes(class)) => {
+                *exact &= class.is_all_ascii();
+                SymbolSet::from_fn(|s| {
+                    class
+                        .ranges()
+                        .iter()
+                        .any(|r| r.start() as usize <= s && s <= r.end() as usize)
+                        && s < 128
+                })
+            }
+        };
+        let to = self.add_state();
+        self.add_transition(from, symbols, to);
+        return to;
+    }
+
+    /// Returns the sorted set of states reachable from the given states through epsilon transitions
+    fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut stack = states.clone();
+        while let Some(state) = stack.pop() {
+            for next in &self.states[state].epsilon {
+                if !states.contains(next) {
+                    states.push(*next);
+                    stack.push(*next);
+                }
+            }
+        }
+        states.sort_unstable();
+        return states;
+    }
+}
+
+/// State of a `Dfa` which has no transitions and accepts nothing
+pub const DEAD: u32 = u32::MAX;
+
+/// Deterministic automaton compiled from an `Nfa` with the subset construction. Symbols with the same transitions
+/// in every state share a class, so the table has a column per class instead of per symbol
+#[derive(Clone)]
+pub struct Dfa<A> {
+    classes: [u8; SYMBOLS],
+    class_count: usize,
+    /// Next state of each state and class, `DEAD` if there is none
+    transitions: Vec<u32>,
+    /// Values accepted by each state, from the highest priority to the lowest
+    accepts: Vec<Vec<A>>,
+}
+
+impl<A: Clone> Dfa<A> {
+    pub const START: u32 = 0;
+
+    pub fn new(nfa: &Nfa<A>) -> Dfa<A> {
+        let start = nfa.closure(vec![Nfa::<A>::START]);
+        let mut ids = HashMap::from([(start.clone(), 0)]);
+        let mut subsets = vec![start];
+        let mut table: Vec<[u32; SYMBOLS]> = Vec::new();
+        let mut accepts = Vec::new();
+
+        let mut i = 0;
+        while i < subsets.len() {
+            let subset = subsets[i].clone();
+            let mut moves: Vec<Vec<usize>> = vec![Vec::new(); SYMBOLS];
+            for state in &subset {
+                for (symbols, to) in &nfa.states[*state].transitions {
+                    for symbol in symbols.iter() {
+                        if !moves[symbol].contains(to) {
+                            moves[symbol].push(*to);
+                        }
+                    }
+                }
+            }
+
+            let mut row = [DEAD; SYMBOLS];
+            for (next, targets) in row.iter_mut().zip(moves) {
+                if targets.is_empty() {
+                    continue;
+                }
+                let targets = nfa.closure(targets);
+                *next = match ids.get(&targets) {
+                    Some(id) => *id,
+                    None => {
+                        ids.insert(targets.clone(), subsets.len() as u32);
+                        subsets.push(targets);
+                        subsets.len() as u32 - 1
+                    }
+                };
+            }
+            table.push(row);
+            let mut accept: Vec<&(i32, A)> = subset
+                .iter()
+                .filter_map(|state| nfa.states[*state].accept.as_ref())
+                .collect();
+            accept.sort_by_key(|(priority, _)| -priority);
+            accepts.push(accept.into_iter().map(|(_, value)| value.clone()).collect());
+            i += 1;
+        }
+
+        // symbols are grouped by the column of their transitions
+        let mut classes = [0; SYMBOLS];
+        let mut columns: HashMap<Vec<u32>, u8> = HashMap::new();
+        for (symbol, class) in classes.iter_mut().enumerate() {
+            let column: Vec<u32> = table.iter().map(|row| row[symbol]).collect();
+            let count = columns.len() as u8;
+            *class = *columns.entry(column).or_insert(count);
+        }
+        let class_count = columns.len();
+        let mut transitions = vec![DEAD; table.len() * class_count];
+        for (state, row) in table.iter().enumerate() {
+            for (symbol, next) in row.iter().enumerate() {
+                transitions[state * class_count + classes[symbol] as usize] = *next;
+            }
+        }
+
+        return Dfa {
+            classes,
+            class_count,
+            transitions,
+            accepts,
+        };
+    }
+
+    pub fn state_count(&self) -> usize {
+        return self.accepts.len();
+    }
+
+    pub fn class_count(&self) -> usize {
+        return self.class_count;
+    }
+
+    /// Returns the state following `state` on a character, which may be `DEAD`
+    pub fn next(&self, state: u32, c: char) -> u32 {
+        return self.next_symbol(state, symbol(c));
+    }
+
+    fn next_symbol(&self, state: u32, symbol: usize) -> u32 {
+        let class = self.classes[symbol] as usize;
+        return self.transitions[state as usize * self.class_count + class];
+    }
+
+    /// Returns the value of the highest priority accepted by a state
+    pub fn accept(&self, state: u32) -> Option<&A> {
+        return self.accepts(state).first();
+    }
+
+    /// Returns the values accepted by a state, from the highest priority to the lowest
+    pub fn accepts(&self, state: u32) -> &[A] {
+        return match self.accepts.get(state as usize) {
+            Some(accepts) => accepts,
+            None => &[],
+        };
+    }
+
+    /// Returns true if some input is accepted by both automata
+    pub fn intersects<B: Clone>(&self, other: &Dfa<B>) -> bool {
+        let mut visited = HashSet::from([(Dfa::<A>::START, Dfa::<B>::START)]);
+        let mut stack = vec![(Dfa::<A>::START, Dfa::<B>::START)];
+        while let Some((a, b)) = stack.pop() {
+            if self.accept(a).is_some() && other.accept(b).is_some() {
+                return true;
+            }
+            for symbol in 0..SYMBOLS {
+                let next = (self.next_symbol(a, symbol), other.next_symbol(b, symbol));
+                if next.0 != DEAD && next.1 != DEAD && visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        return false;
+    }
+
+    /// Returns the value accepted for the longest prefix of the input along with the prefix length in
+    /// characters, or none if no prefix is accepted
+    pub fn longest_match(&self, chars: impl Iterator<Item = char>) -> Option<(&A, usize)> {
+        let mut state = Dfa::<A>::START;
+        let mut longest = None;
+        for (i, c) in chars.enumerate() {
+            state = self.next(state, c);
+            if state == DEAD {
+                break;
+            }
+            if let Some(value) = self.accept(state) {
+                longest = Some((value, i + 1));
+            }
+        }
+        return longest;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{symbol, Dfa, Nfa, SymbolSet, DEAD, XID_CONTINUE, XID_START};
+
+    #[test]
+    fn longest_match() {
+        let mut nfa = Nfa::new();
+        nfa.add_literal("if", 1, "if");
+        nfa.add_literal("=", 1, "=");
+        nfa.add_literal("=>", 1, "=>");
+        let letters = SymbolSet::from_fn(|s| (s as u8).is_ascii_alphabetic() || s == XID_START);
+        let word = nfa.add_state();
+        nfa.add_transition(Nfa::<&str>::START, letters, word);
+        nfa.add_transition(word, letters, word);
+        nfa.set_accept(word, 0, "word");
+        let dfa = Dfa::new(&nfa);
+
+        assert_eq!(dfa.longest_match("if x".chars()), Some((&"if", 2)));
+        assert_eq!(dfa.longest_match("iffy".chars()), Some((&"word", 4)));
+        assert_eq!(dfa.longest_match("été".chars()), Some((&"word", 3)));
+        assert_eq!(dfa.longest_match("=>x".chars()), Some((&"=>", 2)));
+        assert_eq!(dfa.longest_match("=x".chars()), Some((&"=", 1)));
+        assert_eq!(dfa.longest_match("1".chars()), None);
+
+        // every letter but `i` behaves the same, `=`, `>`, `f` and `i` have their own classes
+        assert_eq!(dfa.class_count(), 6);
+        assert_eq!(dfa.next(Dfa::<&str>::START, '1'), DEAD);
+    }
+
+    #[test]
+    fn epsilon() {
+        // `a` followed by any number of `b`, through an epsilon transition to a shared loop
+        let mut nfa = Nfa::new();
+        let a = nfa.add_state();
+        let b = nfa.add_state();
+        nfa.add_transition(Nfa::<()>::START, SymbolSet::of("a"), a);
+        nfa.add_epsilon(a, b);
+        nfa.add_transition(b, SymbolSet::of("b"), b);
+        nfa.set_accept(b, 0, ());
+        let dfa = Dfa::new(&nfa);
+        assert_eq!(dfa.longest_match("abbbc".chars()), Some((&(), 4)));
+        assert_eq!(dfa.longest_match("ba".chars()), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let mut nfa = Nfa::new();
+        assert_eq!(nfa.add_pattern("^[a-z_][a-z0-9]*$", 0, "word"), Ok(true));
+        assert_eq!(nfa.add_pattern("0x[0-9A-F]{1,4}", 1, "hex"), Ok(true));
+        let dfa = Dfa::new(&nfa);
+        assert_eq!(dfa.longest_match("_a1 b".chars()), Some((&"word", 3)));
+        assert_eq!(dfa.longest_match("0xFF1AB".chars()), Some((&"hex", 6)));
+        assert_eq!(dfa.longest_match("0".chars()), None);
+
+        // non ASCII classes are widened to the symbols of their characters
+        let mut greek = Nfa::new();
+        assert_eq!(greek.add_pattern(r"\p{Greek}+", 0, ()), Ok(false));
+        let greek = Dfa::new(&greek);
+        assert_eq!(greek.longest_match("αβ".chars()), Some((&(), 2)));
+        assert!(!greek.intersects(&dfa));
+        let mut x = Nfa::new();
+        x.add_pattern("x+", 0, ()).unwrap();
+        assert!(Dfa::new(&x).intersects(&dfa));
+        assert!(nfa.add_pattern("(", 0, "").is_err());
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(symbol('a'), 'a' as usize);
+        assert_eq!(symbol('é'), XID_START);
+        assert_eq!(symbol('\u{301}'), XID_CONTINUE);
+        let set = SymbolSet::of("a😀");
+        assert!(set.contains('a' as usize) && set.contains(symbol('😀')));
+        assert!(!set.contains('b' as usize));
+    }
+}
@@ -1,178 +1,1231 @@
-use once_cell::sync::Lazy;
-use regex::Regex;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{self, BufRead};
-use std::string::FromUtf8Error;
-use std::{fmt, fs};
+use std::io::{self, BufRead, Read};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::Chars;
+use std::{fmt, mem};
 
-use crate::token::{self, Token};
-use crate::token::{Dictionary, TokenType};
+use unicode_normalization::UnicodeNormalization;
+use unicode_xid::UnicodeXID;
+
+use crate::dictionary::{self, Record};
+use crate::literal::{self, LiteralError};
+use crate::scanner::{Lexeme, Scanner};
+use crate::source::{Edit, SourceMap};
+use crate::token::{self, FileId, Span, Token};
+use crate::token::{CommentKind, Dictionary, TemplateSegment, TokenType};
+
+/// Default number of spaces of an indentation level
+pub const DEFAULT_INDENT_WIDTH: usize = 4;
+
+/// Default maximum number of characters of an identifier
+pub const DEFAULT_MAX_IDENTIFIER_LEN: usize = 31;
 
 pub struct Lexer {
     dictionary: Dictionary,
+    scanner: Scanner,
+    indent_width: usize,
+    max_identifier_len: usize,
+    /// Whether lexical errors are recorded as diagnostics instead of aborting the scan
+    recovery: bool,
+    /// Whether whitespace is kept as trivia so the source can be reconstructed from the tokens
+    lossless: bool,
+    /// Files scanned by this lexer, indexed by their `FileId`
+    files: Vec<SourceFile>,
+    /// File currently being scanned
+    file: FileId,
+    /// Errors recorded while scanning the last file in recovery mode
+    diagnostics: Vec<LexerError>,
+}
+
+struct SourceFile {
+    path: Option<String>,
+    map: SourceMap,
+    /// State at the start of each line of the last scan, followed by the state at the end of the source
+    checkpoints: Vec<Checkpoint>,
+    /// Number of tokens produced by the last scan
+    tokens: usize,
+}
+
+/// State of a scan at the start of a line, from which the line can be scanned again after an edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Checkpoint {
+    /// Byte offset of the start of the line
+    offset: usize,
+    indentation: Indentation,
+    block_comment: Option<BlockComment>,
+    content: bool,
+    /// Number of tokens produced by the previous lines
+    tokens: usize,
+}
+
+/// State of a scan carried across lines
+struct ScanState {
+    /// Number of the next line
+    line: u32,
+    indentation: Indentation,
+    block_comment: Option<BlockComment>,
+    /// Whether the current line has tokens other than comments, a line continues past a line break within a
+    /// block comment
+    content: bool,
+    /// Text of the lines read so far
+    text: String,
+    /// Number of tokens produced so far
+    emitted: usize,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl ScanState {
+    fn checkpoint(&self) -> Checkpoint {
+        return Checkpoint {
+            offset: self.text.len(),
+            indentation: self.indentation.clone(),
+            block_comment: self.block_comment.clone(),
+            content: self.content,
+            tokens: self.emitted,
+        };
+    }
+}
+
+/// Lazy stream of the tokens of a source, see `Lexer::tokens`
+pub struct Tokens<'a, R: BufRead> {
+    lexer: &'a mut Lexer,
+    reader: R,
+    state: ScanState,
+    /// Tokens of the last line scanned which were not pulled yet
+    pending: VecDeque<Token>,
+    done: bool,
+}
+
+impl<R: BufRead> Iterator for Tokens<'_, R> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            let mut tokens = Vec::new();
+            let scanned = self
+                .lexer
+                .scan_next(&mut self.reader, &mut self.state, &mut tokens);
+            match scanned {
+                Ok(more) => self.done = !more,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            self.pending.extend(tokens);
+        }
+        return self.pending.pop_front().map(Ok);
+    }
+}
+
+impl<R: BufRead> Drop for Tokens<'_, R> {
+    /// keep the text read so far in the source map of the source, so the tokens pulled can be located
+    fn drop(&mut self) {
+        let file = &mut self.lexer.files[self.lexer.file.0 as usize];
+        file.map = SourceMap::new(mem::take(&mut self.state.text));
+        file.checkpoints = mem::take(&mut self.state.checkpoints);
+        file.tokens = self.state.emitted;
+    }
+}
+
+/// Indentation state carried across lines, used to produce `Indent` and `Dedent` tokens
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Indentation {
+    /// Columns of the enclosing indentation levels, the outermost level is always zero
+    stack: Vec<usize>,
+    /// Character used for indentation in the file, set by the first indented line
+    style: Option<char>,
+}
+
+/// Block comment left open at the end of a line, carried across lines until it is closed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BlockComment {
+    /// Number of nested openers which are not closed yet
+    depth: usize,
+    start: token::Position,
+    /// Byte offset of the opener in the file
+    offset: usize,
+    text: String,
+    /// Delimiters of the comment, `/*` and `*/` unless registered otherwise in the dictionary
+    opener: String,
+    closer: String,
+}
+
+/// Line being scanned, used to locate its tokens by byte offset
+struct SourceLine {
+    number: u32,
+    /// Byte offset of the start of the line in the file
+    offset: usize,
+    /// Byte offset of each character within the line, followed by the length of the line
+    cols: Vec<usize>,
+}
+
+impl SourceLine {
+    fn new(number: u32, offset: usize, text: &str) -> SourceLine {
+        let mut cols: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        cols.push(text.len());
+        return SourceLine {
+            number,
+            offset,
+            cols,
+        };
+    }
+
+    /// Returns the byte offset in the file of a column of the line
+    fn offset(&self, col: u32) -> usize {
+        let col = (col as usize).min(self.cols.len() - 1);
+        return self.offset + self.cols[col];
+    }
 }
 
 impl Lexer {
     pub fn new() -> Lexer {
         return Lexer {
             dictionary: Dictionary::new(),
+            scanner: Scanner::new(),
+            indent_width: DEFAULT_INDENT_WIDTH,
+            max_identifier_len: DEFAULT_MAX_IDENTIFIER_LEN,
+            recovery: false,
+            lossless: false,
+            files: Vec::new(),
+            file: FileId(0),
+            diagnostics: Vec::new(),
         };
     }
 
+    /// Create a lexer resolving words with a custom dictionary, e.g. one extended with the tokens of an engine
+    pub fn from_dictionary(dictionary: Dictionary) -> Lexer {
+        let mut lexer = Lexer::new();
+        lexer.scanner = Scanner::from_dictionary(&dictionary);
+        lexer.dictionary = dictionary;
+        return lexer;
+    }
+
+    /// Set the number of spaces of an indentation level, a tab always counts as a single level
+    pub fn set_indent_width(&mut self, width: usize) {
+        assert!(width > 0, "indent width must be greater than zero");
+        self.indent_width = width;
+    }
+
+    /// Set the maximum number of characters of an identifier, after normalisation
+    pub fn set_max_identifier_len(&mut self, len: usize) {
+        self.max_identifier_len = len;
+    }
+
+    /// Enable the recovery mode, in which a lexical error is recorded as a diagnostic and replaced by an
+    /// `Error` token so a single scan reports every problem of a file. IO errors always abort the scan
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+    }
+
+    /// Enable the lossless mode, in which every character of the source belongs to exactly one token, with
+    /// whitespace, including the indentation and the line breaks of blank lines, kept as `Whitespace` tokens.
+    /// Concatenating the spans of the tokens then reproduces the source
+    pub fn set_lossless(&mut self, lossless: bool) {
+        self.lossless = lossless;
+    }
+
+    /// Add a record to the dictionary of the lexer, e.g. an alias of a keyword, recompiling its scanner. Records
+    /// of a higher priority win over the keywords and the identifier pattern of the same length
+    pub fn add_record(
+        &mut self,
+        record: Record,
+        lexeme: Lexeme,
+    ) -> std::result::Result<(), dictionary::Error> {
+        self.dictionary.add_record(record, lexeme)?;
+        self.scanner = Scanner::from_dictionary(&self.dictionary);
+        return Ok(());
+    }
+
+    /// Errors recorded while scanning the last file in recovery mode
+    pub fn diagnostics(&self) -> &[LexerError] {
+        return &self.diagnostics;
+    }
+
+    /// Path of a file scanned by this lexer, sources scanned from memory or readers have no path
+    pub fn file_path(&self, file: FileId) -> Option<&str> {
+        return self.files.get(file.0 as usize)?.path.as_deref();
+    }
+
+    /// Line index of a source scanned by this lexer, covering the text read before the scan ended
+    pub fn source_map(&self, file: FileId) -> Option<&SourceMap> {
+        return self.files.get(file.0 as usize).map(|f| &f.map);
+    }
+
     /// scan a source file, returning a stream of tokens
     pub fn scan_file(&mut self, path: &String) -> Result<Vec<Token>> {
-        let mut file = open_file(path)?;
-        let mut line_buffer = Vec::<u8>::new();
-        let mut tokens: Vec<Token> = Vec::<Token>::new();
-        let mut line = 0;
-        let mut char_buffer: Vec<char> = Vec::new();
-        let mut char_buffer_start: token::Position = token::Position { line: 0, col: 0 };
-        let mut string_literal_start: Option<token::Position> = None;
-        fn assert_push_buffer(
-            tokens: &mut Vec<Token>,
-            char_buffer: &mut Vec<char>,
-            char_buffer_start: &mut token::Position,
-            dictionary: &Dictionary,
-        ) -> Result<()> {
-            if !char_buffer.is_empty() {
-                let value: String = char_buffer.iter().collect();
-                match dictionary.get(&value) {
-                    None => {
-                        return Err(LexerError {
-                            kind: ErrorKind::InvalidKeyword(value),
-                        })
+        self.begin(Some(path.clone()));
+        let file = self.open_file(path)?;
+        return self.tokens_of(file).collect();
+    }
+
+    /// scan a source held in memory, such as an editor buffer or a line of the REPL
+    pub fn scan_str(&mut self, source: &str) -> Result<Vec<Token>> {
+        return self.tokens(source.as_bytes()).collect();
+    }
+
+    pub fn scan_reader(&mut self, reader: impl Read) -> Result<Vec<Token>> {
+        return self.tokens(io::BufReader::new(reader)).collect();
+    }
+
+    /// Returns a lazy stream of the tokens of a source, which is scanned a line at a time as tokens are pulled.
+    /// The stream ends after the first error, unless in recovery mode
+    pub fn tokens<R: BufRead>(&mut self, reader: R) -> Tokens<'_, R> {
+        self.begin(None);
+        return self.tokens_of(reader);
+    }
+
+    /// register a new source, which the tokens and errors of the next scan belong to
+    fn begin(&mut self, path: Option<String>) {
+        self.file = FileId(self.files.len() as u32);
+        self.files.push(SourceFile {
+            path,
+            map: SourceMap::new(String::new()),
+            checkpoints: Vec::new(),
+            tokens: 0,
+        });
+        self.diagnostics.clear();
+    }
+
+    fn tokens_of<R: BufRead>(&mut self, reader: R) -> Tokens<'_, R> {
+        return Tokens {
+            lexer: self,
+            reader,
+            state: ScanState {
+                line: 0,
+                indentation: Indentation {
+                    stack: vec![0],
+                    style: None,
+                },
+                block_comment: None,
+                content: false,
+                text: String::new(),
+                emitted: 0,
+                checkpoints: Vec::new(),
+            },
+            pending: VecDeque::new(),
+            done: false,
+        };
+    }
+
+    /// Re-lex a file after an edit of its text, returning the updated token stream of the file. `tokens` must be
+    /// the stream of the last scan of the file, of which only the lines from the one containing the start of the
+    /// edit are scanned again, until a line after the edit starts outside of a block comment with the same
+    /// indentation as in the old stream. The tokens following it are reused, moved by the size of the edit.
+    /// The diagnostics only contain the errors of the lines scanned again
+    pub fn relex(&mut self, file: FileId, tokens: &[Token], edit: &Edit) -> Result<Vec<Token>> {
+        let error = |kind| LexerError {
+            kind,
+            file,
+            span: None,
+            pos: None,
+        };
+        let Some(source) = self.files.get(file.0 as usize) else {
+            return Err(error(ErrorKind::UnknownFile));
+        };
+        let old_text = source.map.text();
+        if source.tokens != tokens.len() {
+            return Err(error(ErrorKind::StaleTokens(source.tokens, tokens.len())));
+        }
+        let Some(text) = edit.apply(old_text) else {
+            return Err(error(ErrorKind::InvalidEdit(edit.range.clone())));
+        };
+        let edit_end = edit.range.start + edit.text.len();
+        let delta = edit.text.len() as isize - edit.range.len() as isize;
+
+        // the scan is resumed from the line containing the start of the edit, with the text preceding it
+        let first_line = source.map.line(edit.range.start) as usize;
+        let checkpoint = source.checkpoints.get(first_line).cloned();
+        let checkpoint = checkpoint.unwrap_or_else(|| source.checkpoints[0].clone());
+        let mut state = ScanState {
+            line: first_line as u32,
+            indentation: checkpoint.indentation.clone(),
+            block_comment: checkpoint.block_comment.clone(),
+            content: checkpoint.content,
+            text: text[..checkpoint.offset].to_string(),
+            emitted: checkpoint.tokens,
+            checkpoints: source.checkpoints[..first_line].to_vec(),
+        };
+        let mut old_checkpoints = mem::take(&mut self.files[file.0 as usize].checkpoints);
+        self.file = file;
+        self.diagnostics.clear();
+
+        let mut reader = &text.as_bytes()[checkpoint.offset..];
+        let mut relexed = tokens[..checkpoint.tokens].to_vec();
+        loop {
+            // lines after the edit are compared with the line they were moved from
+            let offset = state.text.len();
+            let old_line = match offset >= edit_end {
+                true => old_checkpoints
+                    .binary_search_by_key(&(offset as isize - delta), |c| c.offset as isize),
+                false => Err(0),
+            };
+            if let Ok(old_line) = old_line {
+                let old = &old_checkpoints[old_line];
+                let synchronised = old.indentation == state.indentation
+                    && old.content == state.content
+                    && old.block_comment.is_none()
+                    && state.block_comment.is_none();
+                if synchronised {
+                    let lines = state.line as i64 - old_line as i64;
+                    let moved = state.emitted as isize - old.tokens as isize;
+                    for token in &tokens[old.tokens..] {
+                        relexed.push(token.shifted(delta, lines));
                     }
-                    Some(kind) => {
-                        tokens.push(Token::new(
-                            kind,
-                            char_buffer_start.line,
-                            char_buffer_start.col,
-                        ));
-                        char_buffer.clear();
+                    for mut checkpoint in old_checkpoints.drain(old_line..) {
+                        checkpoint.offset = checkpoint.offset.checked_add_signed(delta).unwrap();
+                        checkpoint.tokens = checkpoint.tokens.checked_add_signed(moved).unwrap();
+                        state.checkpoints.push(checkpoint);
                     }
+                    break;
                 }
             }
-            return Ok(());
-        }
-
-        while file.read_until(b'\n', &mut line_buffer)? != 0 {
-            let line_string = String::from_utf8(line_buffer)?;
-            let mut line_chars = line_string.chars().peekable();
-            let mut col = 0;
-
-            // push the current level of indentation
-            let indent_level = self.dictionary.consume_indentation(&mut line_chars);
-            tokens.push(Token::new(TokenType::Indentation(indent_level), line, col));
-
-            while let Some(c) = line_chars.next() {
-                // we're currently inside a string literal
-                if let Some(start_pos) = string_literal_start {
-                    if self.dictionary.is_string_literal_opener(&c) {
-                        string_literal_start = None;
-                        tokens.push(Token::new(
-                            TokenType::LiteralString(char_buffer.iter().collect()),
-                            start_pos.line,
-                            start_pos.col,
-                        ));
-                        char_buffer.clear();
-                        char_buffer_start = token::Position { line, col }
-                    } else {
-                        char_buffer.push(c);
+
+            let mut line_tokens = Vec::new();
+            let more = self.scan_next(&mut reader, &mut state, &mut line_tokens);
+            relexed.append(&mut line_tokens);
+            match more {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => {
+                    // the file keeps its last consistent state
+                    self.files[file.0 as usize].checkpoints = old_checkpoints;
+                    return Err(e);
+                }
+            }
+        }
+
+        let source = &mut self.files[file.0 as usize];
+        source.map = SourceMap::new(text);
+        source.checkpoints = state.checkpoints;
+        source.tokens = relexed.len();
+        return Ok(relexed);
+    }
+
+    /// scan the next line of a reader, recording the state the line starts with so it can be scanned again
+    /// after an edit
+    fn scan_next(
+        &mut self,
+        reader: &mut impl BufRead,
+        state: &mut ScanState,
+        tokens: &mut Vec<Token>,
+    ) -> Result<bool> {
+        state.checkpoints.push(state.checkpoint());
+        let more = self.scan_next_line(reader, state, tokens)?;
+        state.emitted += tokens.len();
+        return Ok(more);
+    }
+
+    /// scan the next line of a reader, returning false once the end of the source is reached and the remaining
+    /// indentation levels are closed
+    fn scan_next_line(
+        &mut self,
+        reader: &mut impl BufRead,
+        state: &mut ScanState,
+        tokens: &mut Vec<Token>,
+    ) -> Result<bool> {
+        let mut line_buffer = Vec::<u8>::new();
+        let read = reader.read_until(b'\n', &mut line_buffer);
+        if read.map_err(|e| self.io_error(e))? == 0 {
+            self.scan_end(state, tokens)?;
+            return Ok(false);
+        }
+        let line = state.line;
+        state.line += 1;
+
+        let line_string = match String::from_utf8(line_buffer) {
+            Ok(line_string) => line_string,
+            Err(e) => {
+                // the line is skipped in recovery mode, as the invalid bytes could be part of any token
+                let lossy = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                let source_line = SourceLine::new(line, state.text.len(), &lossy);
+                state.text.push_str(&lossy);
+                let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+                let col = String::from_utf8_lossy(valid).chars().count() as u32;
+                let error = self.error(ErrorKind::InvalidUtf8, &source_line, col, 1);
+                if !self.recovery {
+                    return Err(error);
+                }
+                self.diagnostics.push(error);
+
+                // the error token covers the content of the line, while the error points at the first
+                // invalid byte
+                let line_break = line_break_len(&lossy);
+                let len = lossy.chars().count() - line_break;
+                let kind = TokenType::Error(lossy.chars().take(len).collect());
+                tokens.push(self.token(kind, &source_line, 0, len));
+                tokens.push(self.token(TokenType::Newline, &source_line, len as u32, line_break));
+                return Ok(true);
+            }
+        };
+        let source_line = SourceLine::new(line, state.text.len(), &line_string);
+        state.text.push_str(&line_string);
+        let mut line_chars = line_string.chars().peekable();
+        let in_comment = state.block_comment.is_some();
+        let indent = match in_comment {
+            true => String::new(),
+            false => self.dictionary.consume_indentation(&mut line_chars),
+        };
+
+        let col = indent.chars().count() as u32;
+        let mut line_tokens = Vec::new();
+        self.scan_line(
+            &mut line_chars,
+            &source_line,
+            col,
+            &mut line_tokens,
+            &mut state.block_comment,
+        )?;
+
+        // blank and comment-only lines do not affect the indentation, neither do lines starting within a
+        // block comment
+        let blank = line_tokens.iter().all(|t| t.kind().is_trivia());
+        if !blank && !in_comment {
+            self.scan_indentation(&indent, &source_line, &mut state.indentation, tokens)?;
+        } else if self.lossless && !indent.is_empty() {
+            let len = indent.chars().count();
+            tokens.push(self.token(TokenType::Whitespace(indent), &source_line, 0, len));
+        }
+        tokens.append(&mut line_tokens);
+
+        // the newline token covers the line break, which is missing on the last line of some files, the line
+        // breaks of blank lines are whitespace and those within a block comment are part of the comment
+        let line_break = line_break_len(&line_string);
+        let end = line_string.chars().count() - line_break;
+        state.content |= !blank;
+        if state.block_comment.is_some() {
+            return Ok(true);
+        }
+        if state.content {
+            state.content = false;
+            tokens.push(self.token(TokenType::Newline, &source_line, end as u32, line_break));
+        } else if self.lossless && line_break > 0 {
+            let kind =
+                TokenType::Whitespace(line_string[line_string.len() - line_break..].to_string());
+            tokens.push(self.token(kind, &source_line, end as u32, line_break));
+        }
+        return Ok(true);
+    }
+
+    /// push the tokens closing a source at its end
+    fn scan_end(&mut self, state: &mut ScanState, tokens: &mut Vec<Token>) -> Result<()> {
+        // an unterminated comment covers the rest of the file
+        if let Some(comment) = state.block_comment.take() {
+            let error = LexerError {
+                kind: ErrorKind::UnterminatedComment,
+                file: self.file,
+                span: Some(self.span(comment.offset, state.text.len())),
+                pos: Some(comment.start),
+            };
+            let text = state.text[comment.offset..].to_string();
+            self.recover(error, text, tokens)?;
+        }
+
+        // close all of the remaining indentation levels
+        let eof = SourceLine::new(state.line, state.text.len(), "");
+        for _ in 1..state.indentation.stack.len() {
+            tokens.push(self.token(TokenType::Dedent, &eof, 0, 0));
+        }
+        state.indentation.stack.truncate(1);
+        return Ok(());
+    }
+
+    /// push the `Indent` or `Dedent` tokens of a line with the given leading whitespace
+    fn scan_indentation(
+        &mut self,
+        indent: &str,
+        line: &SourceLine,
+        indentation: &mut Indentation,
+        tokens: &mut Vec<Token>,
+    ) -> Result<()> {
+        let len = indent.chars().count();
+
+        // a file is either indented with tabs or spaces, but never both, the line keeps the current level
+        // in recovery mode
+        for c in indent.chars() {
+            match indentation.style {
+                None => indentation.style = Some(c),
+                Some(style) if style != c => {
+                    let error = self.error(ErrorKind::MixedIndentation, line, 0, len);
+                    return self.recover(error, indent.to_string(), tokens);
+                }
+                _ => (),
+            }
+        }
+
+        let columns = match indentation.style {
+            Some('\t') => len * self.indent_width,
+            _ if !len.is_multiple_of(self.indent_width) => {
+                let kind = ErrorKind::InvalidIndentWidth(len, self.indent_width);
+                let error = self.error(kind, line, 0, len);
+                return self.recover(error, indent.to_string(), tokens);
+            }
+            _ => len,
+        };
+
+        let current = *indentation.stack.last().unwrap();
+        if columns > current {
+            indentation.stack.push(columns);
+            tokens.push(self.token(TokenType::Indent, line, 0, 0));
+        }
+        while columns < *indentation.stack.last().unwrap() {
+            indentation.stack.pop();
+            tokens.push(self.token(TokenType::Dedent, line, 0, 0));
+            if columns > *indentation.stack.last().unwrap() {
+                let error = self.error(ErrorKind::UnmatchedDedent, line, 0, len);
+                return self.recover(error, indent.to_string(), tokens);
+            }
+        }
+
+        // in lossless mode the indentation is kept as whitespace following the indents and dedents, unless it is
+        // covered by the error token of an invalid indentation
+        if self.lossless && len > 0 {
+            let kind = TokenType::Whitespace(indent.to_string());
+            tokens.push(self.token(kind, line, 0, len));
+        }
+        return Ok(());
+    }
+
+    /// scan the rest of a line starting from the given column, appending the tokens found into `tokens`, a
+    /// block comment left open by a previous line is continued first
+    fn scan_line(
+        &mut self,
+        line_chars: &mut Peekable<Chars>,
+        line: &SourceLine,
+        mut col: u32,
+        tokens: &mut Vec<Token>,
+        block_comment: &mut Option<BlockComment>,
+    ) -> Result<()> {
+        self.continue_block_comment(line_chars, line, &mut col, block_comment, tokens);
+
+        while let Some(c) = line_chars.next() {
+            // matrix literals are scanned as a whole, as the whitespace separating their elements is significant.
+            // A `(` directly following a call target such as `f(1 2)` opens the arguments of a call, and a `v(`
+            // directly following a `.` is a call to a method named `v`
+            let last = tokens.iter().rev().find(|t| !t.kind().is_trivia());
+            let adjacent = last.filter(|t| t.span().end == self.locate(line, col, 0).0.start);
+            let opens_matrix = match c {
+                '(' => !adjacent.is_some_and(|t| is_call_target(t.kind())),
+                'v' => {
+                    line_chars.peek() == Some(&'(')
+                        && !adjacent.is_some_and(|t| t.kind() == &TokenType::Dot)
+                }
+                _ => false,
+            };
+            if opens_matrix {
+                let mut ahead = line_chars.clone();
+                if c == 'v' {
+                    ahead.next();
+                }
+                if let Some(items) = matrix_items(&self.scanner, ahead) {
+                    let open = match c {
+                        'v' => TokenType::VectorOpen,
+                        _ => TokenType::MatrixOpen,
+                    };
+                    col += self.scan_matrix(line_chars, open, items, line, col, tokens)? as u32;
+                    continue;
+                }
+            }
+
+            // a `-` is scanned along with the number following it when it is the sign of the number
+            let last = tokens.iter().rev().find(|t| !t.kind().is_trivia());
+            if is_number_opener(&c, line_chars.peek(), last) {
+                let value = consume_number(&self.scanner, c, line_chars);
+                let len = value.chars().count();
+                self.push_lexeme(Some(Lexeme::Number), value, line, col, tokens)?;
+                col += len as u32;
+                continue;
+            }
+
+            // string and char literals are scanned until their closing quote, which must be on the same line
+            if self.dictionary.is_string_literal_opener(&c) {
+                let raw = match consume_quoted(c, line_chars) {
+                    Ok(raw) => raw,
+                    Err(raw) => {
+                        let len = raw.chars().count() + 1;
+                        let error = self.error(ErrorKind::UnterminatedString, line, col, len);
+                        let text = format!("{}{}", c, raw);
+                        return self.recover(error, text, tokens);
                     }
-                } else {
-                    // on a breaker character we asserts that the current buffer resolves to a valid token
-                    if self.dictionary.is_breaker(&c) {
-                        assert_push_buffer(
-                            &mut tokens,
-                            &mut char_buffer,
-                            &mut char_buffer_start,
-                            &self.dictionary,
-                        )?;
+                };
+                let len = raw.chars().count() + 2;
+                match self.scan_quoted(c, &raw, line, col) {
+                    Ok(kind) => tokens.push(self.token(kind, line, col, len)),
+                    Err(error) => {
+                        let text = format!("{}{}{}", c, raw, c);
+                        self.recover(error, text, tokens)?;
                     }
+                }
+                col += len as u32;
+                continue;
+            }
 
-                    // if we found a string literal opener, start reading into the string literal buffer
-                    if self.dictionary.is_string_literal_opener(&c) {
-                        string_literal_start = Some(token::Position { line, col });
-                    }
-                    // if we found a comment opener character, push the rest as a comment
-                    else if self.dictionary.is_comment_opener(&c) {
-                        let mut comment_str = Vec::new();
-                        while let Some(c) = line_chars.next() {
-                            comment_str.push(c);
-                        }
-                        if comment_str.len() > 0 {
-                            tokens.push(Token::new(
-                                TokenType::Comment(comment_str.into_iter().collect()),
-                                line,
-                                col,
-                            ));
-                        }
-                        break;
-                    } else if !self.dictionary.is_ignore(&c) {
-                        if char_buffer.len() == 0 {
-                            char_buffer_start = token::Position { line, col }
-                        }
-                        char_buffer.push(c);
+            // a `#` is only a colour in place of an operand following another token of the line, such as
+            // `color: #FF0000`, so that comments such as `#facade` are left alone. A comparison such as
+            // `color > #808080` is not the end of a component access
+            let operand =
+                last.is_some_and(|t| t.kind() != &TokenType::Greater && t.kind().ends_operand());
+            if c == '#' && last.is_some() && !operand {
+                if let Some((color, digits)) = consume_color(line_chars) {
+                    let len = digits.len() + 1;
+                    tokens.push(self.token(TokenType::LiteralColor(color), line, col, len));
+                    col += len as u32;
+                    continue;
+                }
+            }
+
+            // comments are checked before operators, as `/` is also the division operator
+            if let Some((kind, len)) = self.dictionary.comment_opener(&c, line_chars) {
+                let start = col;
+                let delimiters = self.dictionary.block_comment(&c, line_chars);
+                let delimiters = delimiters.map(|(o, c)| (o.to_string(), c.to_string()));
+                for _ in 1..len {
+                    line_chars.next();
+                }
+                col += len as u32;
+
+                if let (CommentKind::Block, Some((opener, closer))) = (kind, delimiters) {
+                    *block_comment = Some(BlockComment {
+                        depth: 1,
+                        start: token::Position {
+                            line: line.number,
+                            col: start,
+                        },
+                        offset: line.offset(start),
+                        text: String::new(),
+                        opener,
+                        closer,
+                    });
+                    self.continue_block_comment(line_chars, line, &mut col, block_comment, tokens);
+                    continue;
+                }
+
+                // line comments run until the end of the line, excluding the line break
+                let text: String = line_chars.by_ref().collect();
+                let text: String = text
+                    .chars()
+                    .take(text.chars().count() - line_break_len(&text))
+                    .collect();
+                let len = len + text.chars().count();
+                let kind = match kind {
+                    CommentKind::Doc => TokenType::DocComment(text),
+                    _ => TokenType::Comment(text),
+                };
+                tokens.push(self.token(kind, line, start, len));
+                break;
+            }
+
+            if self.dictionary.is_ignore(&c) {
+                if self.lossless && !is_line_break(c, line_chars.peek()) {
+                    let text = consume_whitespace(c, line_chars);
+                    let len = text.chars().count();
+                    tokens.push(self.token(TokenType::Whitespace(text), line, col, len));
+                    col += len as u32;
+                    continue;
+                }
+                col += 1;
+                continue;
+            }
+
+            // keywords, operators and identifiers are resolved by the longest lexeme of the scanner, characters
+            // which do not start any lexeme are reported along with the rest of their word
+            let (lexeme, text) = match self.scanner.consume(c, line_chars) {
+                Some((lexeme, text)) => (Some(lexeme), text),
+                None => (None, consume_word(c, line_chars)),
+            };
+            let len = text.chars().count();
+            self.push_lexeme(lexeme, text, line, col, tokens)?;
+            col += len as u32;
+        }
+        return Ok(());
+    }
+
+    /// scan a matrix literal following its opener, returning the number of characters of the literal. The rows
+    /// must have the same number of elements, and a column vector must have a single row
+    fn scan_matrix(
+        &mut self,
+        line_chars: &mut Peekable<Chars>,
+        open: TokenType,
+        items: Vec<MatrixItem>,
+        line: &SourceLine,
+        col: u32,
+        tokens: &mut Vec<Token>,
+    ) -> Result<usize> {
+        let opener = match open {
+            TokenType::VectorOpen => "v(",
+            _ => "(",
+        };
+        let text: String = [opener.to_string()]
+            .into_iter()
+            .chain(items.iter().map(|item| item.text()))
+            .collect();
+        let len = text.chars().count();
+        line_chars.nth(len - 2);
+
+        let mut rows = vec![0];
+        for item in &items {
+            match item {
+                MatrixItem::Element(_) => *rows.last_mut().unwrap() += 1,
+                MatrixItem::RowSeparator => rows.push(0),
+                _ => (),
+            }
+        }
+        let kind = match rows.iter().find(|row| **row != rows[0]) {
+            Some(row) => Some(ErrorKind::MatrixRowLength(rows[0], *row)),
+            None if open == TokenType::VectorOpen && rows.len() > 1 => Some(ErrorKind::VectorRows),
+            None => None,
+        };
+        if let Some(kind) = kind {
+            let error = self.error(kind, line, col, len);
+            self.recover(error, text, tokens)?;
+            return Ok(len);
+        }
+
+        let mut start = col + opener.len() as u32;
+        tokens.push(self.token(open, line, col, opener.len()));
+        for item in items {
+            let len = item.text().chars().count();
+            match item {
+                MatrixItem::Element(value) => match literal::parse_number(&value) {
+                    Ok(kind) => tokens.push(self.token(kind, line, start, len)),
+                    Err(e) => {
+                        let error = self.error(ErrorKind::Literal(e), line, start, len);
+                        self.recover(error, value, tokens)?;
                     }
+                },
+                MatrixItem::Whitespace(text) if self.lossless => {
+                    tokens.push(self.token(TokenType::Whitespace(text), line, start, len));
+                }
+                MatrixItem::Whitespace(_) => (),
+                MatrixItem::RowSeparator => {
+                    tokens.push(self.token(TokenType::Semicolon, line, start, len));
+                }
+                MatrixItem::Close => {
+                    tokens.push(self.token(TokenType::ParanthesisClose, line, start, len));
+                }
+            }
+            start += len as u32;
+        }
+        return Ok(len);
+    }
+
+    /// resolve the text of a lexeme starting at column `start` into its token, identifiers are normalised to
+    /// NFC so identifiers which only differ by their encoding are the same
+    fn push_lexeme(
+        &mut self,
+        lexeme: Option<Lexeme>,
+        value: String,
+        line: &SourceLine,
+        start: u32,
+        tokens: &mut Vec<Token>,
+    ) -> Result<()> {
+        let len = value.chars().count();
+        let kind = match lexeme {
+            Some(Lexeme::Token(kind)) => Ok(kind),
+            Some(Lexeme::Identifier) => {
+                let name: String = value.nfc().collect();
+                match name.chars().count() > self.max_identifier_len {
+                    true => Err(ErrorKind::IdentifierTooLong(name, self.max_identifier_len)),
+                    false => Ok(TokenType::Identifier(name)),
+                }
+            }
+            Some(Lexeme::Number) => literal::parse_number(&value).map_err(ErrorKind::Literal),
+            None => Err(ErrorKind::InvalidKeyword(value.clone())),
+        };
+        match kind {
+            Ok(kind) => tokens.push(self.token(kind, line, start, len)),
+            Err(kind) => {
+                let error = self.error(kind, line, start, len);
+                self.recover(error, value, tokens)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Consume the characters of an open block comment until it is closed or the line ends, pushing its token
+    /// once all of its nested comments are closed
+    fn continue_block_comment(
+        &self,
+        chars: &mut Peekable<Chars>,
+        line: &SourceLine,
+        col: &mut u32,
+        block_comment: &mut Option<BlockComment>,
+        tokens: &mut Vec<Token>,
+    ) {
+        let Some(comment) = block_comment.as_mut() else {
+            return;
+        };
+        while let Some(c) = chars.next() {
+            *col += 1;
+            // the closer is checked first, so that comments whose delimiters are the same do not nest
+            let closes = token::starts_with(c, chars, &comment.closer);
+            let opens = !closes && token::starts_with(c, chars, &comment.opener);
+            if !opens && !closes {
+                comment.text.push(c);
+                continue;
+            }
+
+            let delimiter = match closes {
+                true => comment.closer.clone(),
+                false => comment.opener.clone(),
+            };
+            for _ in 1..delimiter.chars().count() {
+                chars.next();
+                *col += 1;
+            }
+            if opens {
+                comment.depth += 1;
+            } else {
+                comment.depth -= 1;
+            }
+            if comment.depth == 0 {
+                let comment = block_comment.take().unwrap();
+                let span = self.span(comment.offset, line.offset(*col));
+                tokens.push(Token::new(
+                    TokenType::Comment(comment.text),
+                    span,
+                    comment.start,
+                ));
+                return;
+            }
+            // nested delimiters are part of the text of the outermost comment
+            comment.text.push_str(&delimiter);
+        }
+    }
+
+    /// resolve the raw content of a quoted literal opened at column `col`, interpolated strings are split into
+    /// their text segments and the tokens of their placeholders
+    fn scan_quoted(
+        &mut self,
+        quote: char,
+        raw: &str,
+        line: &SourceLine,
+        col: u32,
+    ) -> Result<TokenType> {
+        let len = raw.chars().count() + 2;
+        let kind = match literal::parse_quoted(quote, raw) {
+            Ok(kind) => kind,
+            Err(e) => return Err(self.error(ErrorKind::Literal(e), line, col, len)),
+        };
+        if !matches!(kind, TokenType::LiteralString(_)) || !raw.contains(['{', '}']) {
+            return Ok(kind);
+        }
 
-                    // try resolving the current buffer
-                    if !char_buffer.is_empty() {
-                        if let Some(kind) = self.dictionary.get_exact(&char_buffer.iter().collect())
-                        {
-                            tokens.push(Token::new(
-                                kind,
-                                char_buffer_start.line,
-                                char_buffer_start.col,
-                            ));
-                            char_buffer.clear();
-                        }
+        let parts = match literal::split_template(raw) {
+            Ok(parts) => parts,
+            Err(e) => return Err(self.error(ErrorKind::Literal(e), line, col, len)),
+        };
+        let mut segments = Vec::new();
+        for part in parts {
+            match part {
+                literal::TemplatePart::Text(text) => segments.push(TemplateSegment::Text(text)),
+                literal::TemplatePart::Placeholder(offset, expression) => {
+                    // placeholder tokens are positioned in the source, after the quote and the `{`
+                    let mut tokens = Vec::new();
+                    let mut block_comment = None;
+                    let col = col + 2 + offset as u32;
+                    self.scan_line(
+                        &mut expression.chars().peekable(),
+                        line,
+                        col,
+                        &mut tokens,
+                        &mut block_comment,
+                    )?;
+                    if let Some(comment) = block_comment {
+                        let start = comment.start.col;
+                        let len = comment.opener.chars().count() + comment.text.chars().count();
+                        let error = self.error(ErrorKind::UnterminatedComment, line, start, len);
+                        let text = format!("{}{}", comment.opener, comment.text);
+                        self.recover(error, text, &mut tokens)?;
                     }
+                    segments.push(TemplateSegment::Placeholder(tokens));
                 }
+            }
+        }
+        // a string with escaped braces only is not interpolated
+        if let [TemplateSegment::Text(text)] = segments.as_slice() {
+            return Ok(TokenType::LiteralString(text.clone()));
+        }
+        return Ok(TokenType::LiteralTemplate(segments));
+    }
 
-                col += 1;
+    fn span(&self, start: usize, end: usize) -> Span {
+        return Span {
+            file: self.file,
+            start,
+            end,
+        };
+    }
+
+    /// Locate `len` characters from a column of the line in the current file
+    fn locate(&self, line: &SourceLine, col: u32, len: usize) -> (Span, token::Position) {
+        let span = self.span(line.offset(col), line.offset(col + len as u32));
+        let pos = token::Position {
+            line: line.number,
+            col,
+        };
+        return (span, pos);
+    }
+
+    fn token(&self, kind: TokenType, line: &SourceLine, col: u32, len: usize) -> Token {
+        let (span, pos) = self.locate(line, col, len);
+        return Token::new(kind, span, pos);
+    }
+
+    fn error(&self, kind: ErrorKind, line: &SourceLine, col: u32, len: usize) -> LexerError {
+        let (span, pos) = self.locate(line, col, len);
+        return LexerError {
+            kind,
+            file: self.file,
+            span: Some(span),
+            pos: Some(pos),
+        };
+    }
+
+    fn io_error(&self, error: io::Error) -> LexerError {
+        return LexerError {
+            kind: ErrorKind::Io(error.to_string()),
+            file: self.file,
+            span: None,
+            pos: None,
+        };
+    }
+
+    /// Fail with the given error, or in recovery mode record it and push an `Error` token with the source
+    /// text it covers instead
+    fn recover(&mut self, error: LexerError, text: String, tokens: &mut Vec<Token>) -> Result<()> {
+        if !self.recovery {
+            return Err(error);
+        }
+        let (span, pos) = (error.span.unwrap(), error.pos.unwrap());
+        tokens.push(Token::new(TokenType::Error(text), span, pos));
+        self.diagnostics.push(error);
+        return Ok(());
+    }
+
+    fn open_file(&self, path: &String) -> Result<io::BufReader<File>> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(_) => {
+                return Err(LexerError {
+                    kind: ErrorKind::InvalidFile(path.clone()),
+                    file: self.file,
+                    span: None,
+                    pos: None,
+                })
             }
+        };
+        Ok(io::BufReader::new(file))
+    }
+}
 
-            if !char_buffer.is_empty() {
-                assert_push_buffer(
-                    &mut tokens,
-                    &mut char_buffer,
-                    &mut char_buffer_start,
-                    &self.dictionary,
-                )?;
+/// Returns true if the given character starts a numeric literal, a `-` is only treated as the sign of the
+/// literal when it is followed by a digit and does not follow an operand
+fn is_number_opener(c: &char, next: Option<&char>, last: Option<&Token>) -> bool {
+    if c.is_ascii_digit() {
+        return true;
+    }
+    return *c == '-'
+        && next.is_some_and(|n| n.is_ascii_digit())
+        && last.is_none_or(|t| !t.kind().ends_operand());
+}
+
+/// Consume the characters of a numeric literal starting with `first`, which is either a digit or the sign of
+/// the literal
+fn consume_number(scanner: &Scanner, first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    let mut first = first;
+    if first == '-' {
+        value.push(first);
+        first = chars.next().unwrap();
+    }
+    match scanner.consume(first, chars) {
+        Some((_, text)) => value.push_str(&text),
+        None => value.push(first),
+    }
+    return value;
+}
+
+/// Consume the rest of a word which does not start any lexeme, up to the next character which may not continue
+/// an identifier
+fn consume_word(first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::from(first);
+    while let Some(c) = chars.next_if(|c| c.is_xid_continue()) {
+        word.push(c);
+    }
+    return word;
+}
+
+/// Returns true if a `(` directly following the token opens the arguments of a call
+fn is_call_target(kind: &TokenType) -> bool {
+    return kind.identifier().is_some()
+        || matches!(kind, TokenType::ParanthesisClose | TokenType::BracketClose);
+}
+
+/// Part of a matrix literal following its opener
+enum MatrixItem {
+    Element(String),
+    Whitespace(String),
+    RowSeparator,
+    Close,
+}
+
+impl MatrixItem {
+    fn text(&self) -> String {
+        return match self {
+            MatrixItem::Element(text) | MatrixItem::Whitespace(text) => text.clone(),
+            MatrixItem::RowSeparator => ";".to_string(),
+            MatrixItem::Close => ")".to_string(),
+        };
+    }
+}
+
+/// Returns the items of a matrix literal up to its closing parenthesis on the same line, or none if the
+/// parentheses do not hold only numeric elements separated by whitespace or `;`, at least two of them
+fn matrix_items(scanner: &Scanner, mut chars: Peekable<Chars>) -> Option<Vec<MatrixItem>> {
+    let mut items = Vec::new();
+    let mut elements = 0;
+    let mut separated = true;
+    loop {
+        let c = chars.next()?;
+        match c {
+            ')' => break,
+            ';' => {
+                items.push(MatrixItem::RowSeparator);
+                separated = true;
+            }
+            _ if c.is_whitespace() && !is_line_break(c, chars.peek()) => {
+                items.push(MatrixItem::Whitespace(consume_whitespace(c, &mut chars)));
+                separated = true;
+            }
+            _ if separated && is_number_opener(&c, chars.peek(), None) => {
+                items.push(MatrixItem::Element(consume_number(scanner, c, &mut chars)));
+                elements += 1;
+                separated = false;
             }
+            _ => return None,
+        }
+    }
+    if elements < 2 {
+        return None;
+    }
+    items.push(MatrixItem::Close);
+    return Some(items);
+}
 
-            line_buffer = line_string.into_bytes();
-            line_buffer.clear();
-            line += 1;
+/// Consume the digits of a colour literal following a `#`, which must be a whole word of 6 or 8 hexadecimal
+/// digits, nothing is consumed otherwise
+fn consume_color(chars: &mut Peekable<Chars>) -> Option<(token::Color, String)> {
+    let digits: String = chars.clone().take_while(|c| c.is_xid_continue()).collect();
+    let color = literal::parse_color(&digits)?;
+    chars.nth(digits.len() - 1);
+    return Some((color, digits));
+}
+
+/// Consume a run of whitespace within a line, the line break ending the line is not consumed
+fn consume_whitespace(first: char, chars: &mut Peekable<Chars>) -> String {
+    let mut text = String::from(first);
+    while let Some(&c) = chars.peek() {
+        let mut ahead = chars.clone();
+        ahead.next();
+        if !c.is_whitespace() || is_line_break(c, ahead.peek()) {
+            break;
         }
+        text.push(c);
+        chars.next();
+    }
+    return text;
+}
+
+/// Returns true if the character starts the `\n` or `\r\n` line break ending a line
+fn is_line_break(c: char, next: Option<&char>) -> bool {
+    return c == '\n' || (c == '\r' && next == Some(&'\n'));
+}
 
-        return Ok(tokens);
+/// Returns the number of characters of the line break ending a line
+fn line_break_len(line: &str) -> usize {
+    if line.ends_with("\r\n") {
+        return 2;
     }
+    return line.ends_with('\n') as usize;
+}
+
+/// Consume the raw characters of a quoted literal up to its closing quote, the characters consumed are returned
+/// as an error if the line ends before the literal is terminated
+fn consume_quoted(quote: char, chars: &mut Peekable<Chars>) -> std::result::Result<String, String> {
+    let mut raw = String::new();
+    let mut escaped = false;
+    while let Some(&c) = chars.peek() {
+        let mut ahead = chars.clone();
+        ahead.next();
+        if is_line_break(c, ahead.peek()) {
+            return Err(raw);
+        }
+        chars.next();
+        if c == quote && !escaped {
+            return Ok(raw);
+        }
+        escaped = c == '\\' && !escaped;
+        raw.push(c);
+    }
+    return Err(raw);
 }
 
 type Result<T> = std::result::Result<T, LexerError>;
 
-#[derive(Debug, Clone)]
-enum ErrorKind {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
     InvalidKeyword(String),
     InvalidIdentifier(String),
+    IdentifierTooLong(String, usize),
     InvalidFile(String),
+    Io(String),
+    InvalidUtf8,
+    UnterminatedString,
+    UnterminatedComment,
+    MixedIndentation,
+    InvalidIndentWidth(usize, usize),
+    UnmatchedDedent,
+    /// Rows of a matrix literal with a different number of elements, the expected and found number
+    MatrixRowLength(usize, usize),
+    /// Column vector literal with more than one row
+    VectorRows,
+    Literal(LiteralError),
+    /// File re-lexed by `relex` which was not scanned by the lexer
+    UnknownFile,
+    /// Token stream given to `relex` which is not the last scan of the file, the expected and found number of
+    /// tokens
+    StaleTokens(usize, usize),
+    /// Edit given to `relex` whose range is not within the text of the file or not on character boundaries
+    InvalidEdit(Range<usize>),
 }
 
 #[derive(Debug, Clone)]
 pub struct LexerError {
     kind: ErrorKind,
+    file: FileId,
+    /// Source covered by the error, errors about the whole file such as IO errors are not located
+    span: Option<Span>,
+    pos: Option<token::Position>,
 }
 
-impl From<io::Error> for LexerError {
-    fn from(value: io::Error) -> Self {
-        todo!()
+impl LexerError {
+    pub fn kind(&self) -> &ErrorKind {
+        return &self.kind;
+    }
+
+    pub fn file(&self) -> FileId {
+        return self.file;
     }
-}
 
-impl From<FromUtf8Error> for LexerError {
-    fn from(value: FromUtf8Error) -> Self {
-        todo!()
+    pub fn span(&self) -> Option<Span> {
+        return self.span;
+    }
+
+    pub fn pos(&self) -> Option<token::Position> {
+        return self.pos;
     }
 }
 
@@ -182,24 +1235,1179 @@ impl fmt::Display for LexerError {
             ErrorKind::InvalidIdentifier(identifier) => {
                 write!(f, "invalid identifier: {}", identifier)
             }
+            ErrorKind::IdentifierTooLong(identifier, max) => {
+                write!(
+                    f,
+                    "identifier {} is longer than {} characters",
+                    identifier, max
+                )
+            }
             ErrorKind::InvalidKeyword(keyword) => {
                 write!(f, "invalid keyword: {}", keyword)
             }
             ErrorKind::InvalidFile(file) => {
                 write!(f, "invalid file: {}", file)
             }
+            ErrorKind::Io(error) => {
+                write!(f, "failed to read file: {}", error)
+            }
+            ErrorKind::InvalidUtf8 => {
+                write!(f, "invalid UTF-8")
+            }
+            ErrorKind::UnterminatedString => {
+                write!(f, "unterminated string literal")
+            }
+            ErrorKind::UnterminatedComment => {
+                write!(f, "unterminated block comment")
+            }
+            ErrorKind::MixedIndentation => {
+                write!(f, "mixed tabs and spaces in indentation")
+            }
+            ErrorKind::InvalidIndentWidth(spaces, width) => {
+                write!(
+                    f,
+                    "indentation of {} spaces is not a multiple of the indent width {}",
+                    spaces, width
+                )
+            }
+            ErrorKind::UnmatchedDedent => {
+                write!(f, "dedent does not match any enclosing indentation level")
+            }
+            ErrorKind::MatrixRowLength(expected, found) => {
+                write!(
+                    f,
+                    "matrix row has {} elements, expected {} as in the first row",
+                    found, expected
+                )
+            }
+            ErrorKind::VectorRows => {
+                write!(
+                    f,
+                    "column vector must be a single row of elements, such as v(1 2 3)"
+                )
+            }
+            ErrorKind::Literal(error) => {
+                write!(f, "invalid literal: {}", error)
+            }
+            ErrorKind::UnknownFile => {
+                write!(f, "unknown file {}", self.file.0)
+            }
+            ErrorKind::StaleTokens(expected, found) => {
+                write!(
+                    f,
+                    "tokens are not the last scan of the file, expected {} tokens, found {}",
+                    expected, found
+                )
+            }
+            ErrorKind::InvalidEdit(range) => {
+                write!(
+                    f,
+                    "edit range {}..{} is not within the text of the file",
+                    range.start, range.end
+                )
+            }
+        }?;
+        if let Some(pos) = self.pos() {
+            write!(f, " at {}:{}", pos.line + 1, pos.col + 1)?;
         }
+        Ok(())
     }
 }
 
-fn open_file(path: &String) -> Result<io::BufReader<fs::File>> {
-    let file = match File::open(path) {
-        Ok(f) => f,
-        Err(_) => {
-            return Err(LexerError {
-                kind: ErrorKind::InvalidFile(path.clone()),
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::OnceLock;
+    use std::{env, fs};
+
+    use proptest::prelude::*;
+
+    use super::{ErrorKind, Lexer};
+    use crate::dictionary::{self, Record};
+    use crate::literal::LiteralError;
+    use crate::scanner::Lexeme;
+    use crate::source::Edit;
+    use crate::token::{
+        self, Color, Dictionary, ExtensionError, FileId, Float, Integer, Position, ScalarType,
+        Span, TemplateSegment, Token, TokenType,
+    };
+
+    fn scan(source: &str) -> super::Result<Vec<TokenType>> {
+        let tokens = Lexer::new().scan_str(source)?;
+        return Ok(tokens.iter().map(|t| t.kind().clone()).collect());
+    }
+
+    /// token of the first file scanned by a lexer, on the first line of an ascii source
+    fn token(kind: TokenType, col: u32, len: usize) -> Token {
+        let start = col as usize;
+        let span = Span {
+            file: FileId(0),
+            start,
+            end: start + len,
+        };
+        return Token::new(kind, span, Position { line: 0, col });
+    }
+
+    #[test]
+    fn numbers() {
+        let tokens = scan("a: 20 * -2l - 3 + 23.35f\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                TokenType::Colon,
+                TokenType::LiteralInteger(Integer::I32(20)),
+                TokenType::Asterisk,
+                TokenType::LiteralInteger(Integer::I64(-2)),
+                TokenType::Minus,
+                TokenType::LiteralInteger(Integer::I32(3)),
+                TokenType::Plus,
+                TokenType::LiteralFloat(Float::F32(23.35)),
+                TokenType::Newline,
+            ]
+        );
+
+        // a `-` following an index, a call, a record literal or a component access is a binary operator
+        for source in ["a[0]-1\n", "f(x)[1]-2\n", "A {}-3\n", "e<Health>-4\n"] {
+            let tokens = scan(source).unwrap();
+            assert_eq!(tokens[tokens.len() - 3], TokenType::Minus, "{}", source);
+            assert!(matches!(
+                tokens[tokens.len() - 2],
+                TokenType::LiteralInteger(Integer::I32(1..))
+            ));
+        }
+        let tokens = scan("a > -1 && c > #808080\n").unwrap();
+        assert_eq!(tokens[2], TokenType::Minus);
+        assert!(matches!(tokens[7], TokenType::LiteralColor(_)));
+    }
+
+    #[test]
+    fn durations() {
+        let tokens = scan("wait 1h20.5s\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Wait,
+                TokenType::LiteralDuration(3_620_500_000_000),
+                TokenType::Newline,
+            ]
+        );
+
+        // a lone `d` suffix is a 64-bit float, days are written as a compound duration
+        let tokens = scan("wait 10d 10d0s\n").unwrap();
+        assert_eq!(tokens[1], TokenType::LiteralFloat(Float::F64(10.0)));
+        assert_eq!(tokens[2], TokenType::LiteralDuration(864_000_000_000_000));
+    }
+
+    #[test]
+    fn operators() {
+        let tokens = scan("a+=b=>c->d==e!=f<=g>=h&&i||!j?:k::l,[m]{n}<o>=-p\n").unwrap();
+        let operators: Vec<TokenType> = tokens
+            .into_iter()
+            .filter(|t| !matches!(t, TokenType::Identifier(_) | TokenType::Newline))
+            .collect();
+        assert_eq!(
+            operators,
+            vec![
+                TokenType::PlusEqual,
+                TokenType::Lambda,
+                TokenType::Arrow,
+                TokenType::EqualEqual,
+                TokenType::NotEqual,
+                TokenType::LessEqual,
+                TokenType::GreaterEqual,
+                TokenType::And,
+                TokenType::Or,
+                TokenType::Not,
+                TokenType::OptionalColon,
+                TokenType::DoubleColon,
+                TokenType::Comma,
+                TokenType::BracketOpen,
+                TokenType::BracketClose,
+                TokenType::BraceOpen,
+                TokenType::BraceClose,
+                TokenType::Less,
+                TokenType::GreaterEqual,
+                TokenType::Minus,
+            ]
+        );
+    }
+
+    #[test]
+    fn keyword_prefixes() {
+        let tokens = scan("format for iffy\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("format".to_string()),
+                TokenType::For,
+                TokenType::Identifier("iffy".to_string()),
+                TokenType::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let tokens = scan("structure component foreach new self import expose\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Structure,
+                TokenType::Component,
+                TokenType::Foreach,
+                TokenType::New,
+                TokenType::SelfRef,
+                TokenType::Import,
+                TokenType::Expose,
+                TokenType::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn contextual_keywords() {
+        let tokens = scan("on event Increment to one\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::On,
+                TokenType::Event,
+                TokenType::Identifier("Increment".to_string()),
+                TokenType::To,
+                TokenType::Identifier("one".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        // contextual keywords remain usable as identifiers
+        let tokens = scan("(state, event) => state.to - 1\n").unwrap();
+        assert_eq!(tokens[1].identifier(), Some("state"));
+        assert_eq!(tokens[3].identifier(), Some("event"));
+        assert_eq!(tokens[8].identifier(), Some("to"));
+        assert_eq!(tokens[9], TokenType::Minus);
+        assert_eq!(TokenType::If.identifier(), None);
+    }
+
+    #[test]
+    fn strings() {
+        let tokens = scan("print \"a # \\\"b\\\"\" 'c'\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("print".to_string()),
+                TokenType::LiteralString("a # \"b\"".to_string()),
+                TokenType::LiteralChar('c'),
+                TokenType::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let error = scan("a: 'b\nc: \"d\"\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnterminatedString);
+        assert_eq!(error.pos(), Some(Position { line: 0, col: 3 }));
+    }
+
+    #[test]
+    fn templates() {
+        let tokens = scan("m = '{e.sender} says {{{}}}'\n").unwrap();
+        assert_eq!(
+            tokens[2],
+            TokenType::LiteralTemplate(vec![
+                TemplateSegment::Placeholder(vec![
+                    token(TokenType::Identifier("e".to_string()), 6, 1),
+                    token(TokenType::Dot, 7, 1),
+                    token(TokenType::Identifier("sender".to_string()), 8, 6),
+                ]),
+                TemplateSegment::Text(" says {".to_string()),
+                TemplateSegment::Placeholder(vec![]),
+                TemplateSegment::Text("}".to_string()),
+            ])
+        );
+
+        let tokens = scan("m = \"{{a}}\"\n").unwrap();
+        assert_eq!(tokens[2], TokenType::LiteralString("{a}".to_string()));
+    }
+
+    #[test]
+    fn indentation() {
+        let source = "system A\n    reads B\n\n    # comment\n    x =>\n        y\nz\n";
+        let tokens = scan(source).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::System,
+                TokenType::Identifier("A".to_string()),
+                TokenType::Newline,
+                TokenType::Indent,
+                TokenType::Reads,
+                TokenType::Identifier("B".to_string()),
+                TokenType::Newline,
+                TokenType::Comment(" comment".to_string()),
+                TokenType::Identifier("x".to_string()),
+                TokenType::Lambda,
+                TokenType::Newline,
+                TokenType::Indent,
+                TokenType::Identifier("y".to_string()),
+                TokenType::Newline,
+                TokenType::Dedent,
+                TokenType::Dedent,
+                TokenType::Identifier("z".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        // remaining levels are closed at the end of the file
+        let tokens = scan("a\n\tb").unwrap();
+        assert_eq!(
+            &tokens[2..],
+            &[
+                TokenType::Indent,
+                TokenType::Identifier("b".to_string()),
+                TokenType::Newline,
+                TokenType::Dedent,
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let source = "a / b // ratio\n## Doc\n/// Also doc\n//// not doc\nc /* x /* y */ */ d\n";
+        let tokens = scan(source).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                TokenType::ForwardSlash,
+                TokenType::Identifier("b".to_string()),
+                TokenType::Comment(" ratio".to_string()),
+                TokenType::Newline,
+                TokenType::DocComment(" Doc".to_string()),
+                TokenType::DocComment(" Also doc".to_string()),
+                TokenType::Comment("// not doc".to_string()),
+                TokenType::Identifier("c".to_string()),
+                TokenType::Comment(" x /* y */ ".to_string()),
+                TokenType::Identifier("d".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        // block comments spanning multiple lines do not affect the indentation
+        let source = "a\n    /* one\ntwo */\n    b /*\n*/ c\nd\n";
+        let tokens = scan(source).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                TokenType::Newline,
+                TokenType::Comment(" one\ntwo ".to_string()),
+                TokenType::Indent,
+                TokenType::Identifier("b".to_string()),
+                TokenType::Comment("\n".to_string()),
+                TokenType::Identifier("c".to_string()),
+                TokenType::Newline,
+                TokenType::Dedent,
+                TokenType::Identifier("d".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        let error = scan("a\n  /* x /* y */\nb\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnterminatedComment);
+        assert_eq!(error.pos(), Some(Position { line: 1, col: 2 }));
+    }
+
+    #[test]
+    fn indentation_errors() {
+        let error = scan("a\n    b\n\tc\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MixedIndentation);
+        assert_eq!(error.pos(), Some(Position { line: 2, col: 0 }));
+
+        let error = scan("a\n        b\n    c\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnmatchedDedent);
+        assert_eq!(error.pos(), Some(Position { line: 2, col: 0 }));
+
+        let error = scan("a\n   b\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidIndentWidth(3, 4));
+
+        let mut lexer = Lexer::new();
+        lexer.set_indent_width(2);
+        let path = env::temp_dir().join("ecsl-lexer-indent-width-2.ys");
+        fs::write(&path, "a\n  b\n").unwrap();
+        let tokens = lexer.scan_file(&path.to_string_lossy().to_string());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tokens.unwrap()[2].kind(), &TokenType::Indent);
+    }
+
+    #[test]
+    fn number_overflow() {
+        let error = scan("a: 1\nb: 4294967296u\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Literal(LiteralError::Overflow(
+                "4294967296".to_string(),
+                ScalarType::U32
+            ))
+        );
+        assert_eq!(error.pos(), Some(Position { line: 1, col: 3 }));
+    }
+
+    #[test]
+    fn recovery() {
+        let source = "a = 3q + $b\n  c\nd = 'x\ne = \"\\q\" /* open\n";
+        let mut lexer = Lexer::new();
+        lexer.set_recovery(true);
+        let tokens = lexer.scan_str(source);
+
+        let errors: Vec<(TokenType, Position)> = tokens
+            .unwrap()
+            .into_iter()
+            .filter(|t| matches!(t.kind(), TokenType::Error(_)))
+            .map(|t| (t.kind().clone(), t.pos()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    TokenType::Error("3q".to_string()),
+                    Position { line: 0, col: 4 }
+                ),
+                (
+                    TokenType::Error("$b".to_string()),
+                    Position { line: 0, col: 9 }
+                ),
+                (
+                    TokenType::Error("  ".to_string()),
+                    Position { line: 1, col: 0 }
+                ),
+                (
+                    TokenType::Error("'x".to_string()),
+                    Position { line: 2, col: 4 }
+                ),
+                (
+                    TokenType::Error("\"\\q\"".to_string()),
+                    Position { line: 3, col: 4 }
+                ),
+                (
+                    TokenType::Error("/* open\n".to_string()),
+                    Position { line: 3, col: 9 }
+                ),
+            ]
+        );
+
+        let kinds: Vec<&ErrorKind> = lexer.diagnostics().iter().map(|e| e.kind()).collect();
+        assert_eq!(kinds.len(), 6);
+        assert_eq!(kinds[1], &ErrorKind::InvalidKeyword("$b".to_string()));
+        assert_eq!(kinds[2], &ErrorKind::InvalidIndentWidth(2, 4));
+        assert_eq!(kinds[3], &ErrorKind::UnterminatedString);
+        assert_eq!(kinds[5], &ErrorKind::UnterminatedComment);
+
+        let error = &lexer.diagnostics()[0];
+        assert_eq!(lexer.file_path(error.file()), None);
+        assert_eq!(
+            error.span(),
+            Some(Span {
+                file: FileId(0),
+                start: 4,
+                end: 6,
             })
+        );
+    }
+
+    #[test]
+    fn file_errors() {
+        let path = env::temp_dir().join("ecsl-lexer-utf8.ys");
+        fs::write(&path, b"a\nb\xff\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let mut lexer = Lexer::new();
+        let error = lexer.scan_file(&path).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        assert_eq!(error.pos(), Some(Position { line: 1, col: 1 }));
+
+        // the invalid line is replaced by an `Error` token in recovery mode
+        lexer.set_recovery(true);
+        let tokens = lexer.scan_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tokens[2].kind(), &TokenType::Error("b\u{FFFD}".to_string()));
+        assert_eq!(lexer.diagnostics().len(), 1);
+        assert_eq!(lexer.diagnostics()[0].file(), FileId(1));
+
+        let error = lexer.scan_file(&"missing.ys".to_string()).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::InvalidFile("missing.ys".to_string())
+        );
+        assert_eq!(error.span(), None);
+    }
+
+    #[test]
+    fn spans() {
+        let source = "ab = 'ü'\r\n    b /* x\n😀 */ + 1\n";
+        let mut lexer = Lexer::new();
+        let tokens = lexer.scan_str(source).unwrap();
+
+        let map = lexer.source_map(tokens[0].file()).unwrap();
+        assert_eq!(map.text(), source);
+        let slices: Vec<&str> = tokens.iter().map(|t| map.slice(t.span())).collect();
+        assert_eq!(
+            slices,
+            vec![
+                "ab",
+                "=",
+                "'ü'",
+                "\r\n",
+                "",
+                "b",
+                "/* x\n😀 */",
+                "+",
+                "1",
+                "\n",
+                ""
+            ]
+        );
+
+        // positions count characters, while editors are given UTF-16 columns
+        let plus = &tokens[7];
+        assert_eq!(plus.pos(), Position { line: 2, col: 5 });
+        assert_eq!(map.position(plus.span().start), plus.pos());
+        assert_eq!(map.utf8_col(plus.span().start), 8);
+        assert_eq!(map.utf16_col(plus.span().start), 6);
+    }
+
+    #[test]
+    fn streams() {
+        let mut lexer = Lexer::new();
+        let from_reader = lexer.scan_reader("a\n\tb".as_bytes()).unwrap();
+        let from_str = lexer.scan_str("a\n\tb").unwrap();
+        assert_eq!(from_reader.len(), from_str.len());
+        for (a, b) in from_reader.iter().zip(&from_str) {
+            assert_eq!(
+                (a.kind(), a.span().start, a.pos()),
+                (b.kind(), b.span().start, b.pos())
+            );
         }
-    };
-    Ok(io::BufReader::new(file))
+        assert_ne!(from_reader[0].file(), from_str[0].file());
+
+        // tokens are pulled up to the first error
+        let mut tokens = lexer.tokens("a\n$\nb\n".as_bytes());
+        assert_eq!(
+            tokens.next().unwrap().unwrap().kind(),
+            &TokenType::Identifier("a".to_string())
+        );
+        assert_eq!(tokens.next().unwrap().unwrap().kind(), &TokenType::Newline);
+        let error = tokens.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidKeyword("$".to_string()));
+        assert!(tokens.next().is_none());
+        drop(tokens);
+
+        // the source map covers the lines read before the error
+        let map = lexer.source_map(error.file()).unwrap();
+        assert_eq!(map.text(), "a\n$\n");
+    }
+
+    #[test]
+    fn lossless() {
+        let source = "a  = -1 #\n\n  \t\n    b /*\n*/ - 2\r\n\t# c\n";
+        let mut lexer = Lexer::new();
+        lexer.set_lossless(true);
+        let tokens = lexer.scan_str(source).unwrap();
+        let map = lexer.source_map(tokens[0].file()).unwrap();
+        let slices: Vec<&str> = tokens.iter().map(|t| map.slice(t.span())).collect();
+        assert_eq!(slices.concat(), source);
+
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind().clone()).collect();
+        let whitespace = |s: &str| TokenType::Whitespace(s.to_string());
+        assert_eq!(
+            kinds,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                whitespace("  "),
+                TokenType::Equal,
+                whitespace(" "),
+                TokenType::LiteralInteger(Integer::I32(-1)),
+                whitespace(" "),
+                TokenType::Comment("".to_string()),
+                TokenType::Newline,
+                whitespace("\n"),
+                whitespace("  \t"),
+                whitespace("\n"),
+                TokenType::Indent,
+                whitespace("    "),
+                TokenType::Identifier("b".to_string()),
+                whitespace(" "),
+                TokenType::Comment("\n".to_string()),
+                whitespace(" "),
+                TokenType::Minus,
+                whitespace(" "),
+                TokenType::LiteralInteger(Integer::I32(2)),
+                TokenType::Newline,
+                whitespace("\t"),
+                TokenType::Comment(" c".to_string()),
+                whitespace("\n"),
+                TokenType::Dedent,
+            ]
+        );
+
+        // the tokens are the same as without the lossless mode, besides the whitespace
+        let significant: Vec<TokenType> = kinds
+            .into_iter()
+            .filter(|t| !matches!(t, TokenType::Whitespace(_)))
+            .collect();
+        assert_eq!(significant, scan(source).unwrap());
+    }
+
+    /// Scan a source in lossless and recovery mode, asserting the tokens cover the source exactly once
+    fn assert_lossless(source: &str) {
+        let mut lexer = Lexer::new();
+        lexer.set_lossless(true);
+        lexer.set_recovery(true);
+        let tokens = lexer.scan_str(source).unwrap();
+
+        let mut end = 0;
+        for token in &tokens {
+            assert_eq!(token.span().start, end, "gap or overlap before {:?}", token);
+            end = token.span().end;
+        }
+        let map = lexer.source_map(FileId(0)).unwrap();
+        let slices: Vec<&str> = tokens.iter().map(|t| map.slice(t.span())).collect();
+        assert_eq!(slices.concat(), source);
+
+        // whitespace aside, the lossless mode does not change the tokens
+        let mut lexer = Lexer::new();
+        lexer.set_recovery(true);
+        let expected: Vec<TokenType> = lexer
+            .scan_str(source)
+            .unwrap()
+            .iter()
+            .map(|t| t.kind().clone())
+            .collect();
+        let significant: Vec<TokenType> = tokens
+            .iter()
+            .map(|t| t.kind().clone())
+            .filter(|t| !matches!(t, TokenType::Whitespace(_)))
+            .collect();
+        assert_eq!(significant, expected);
+    }
+
+    /// Sources of the docs/examples scripts
+    fn examples() -> Vec<String> {
+        let mut dirs = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/examples")];
+        let mut sources = Vec::new();
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "ys") {
+                    sources.push(fs::read_to_string(path).unwrap());
+                }
+            }
+        }
+        return sources;
+    }
+
+    #[test]
+    fn lossless_examples() {
+        let sources = examples();
+        assert!(!sources.is_empty());
+        for source in sources {
+            assert_lossless(&source);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn lossless_mixed_examples(
+            picks in prop::collection::vec((any::<prop::sample::Index>(), 0..8usize), 0..40),
+        ) {
+            static LINES: OnceLock<Vec<String>> = OnceLock::new();
+            let lines = LINES.get_or_init(|| {
+                let sources = examples();
+                sources.iter().flat_map(|s| s.split_inclusive('\n')).map(String::from).collect()
+            });
+
+            // lines of the examples are shuffled, re-indented and broken up with fragments which are hard to
+            // scan, such as unterminated literals and comments or carriage returns
+            let fragments = ["", "\t", "  ", "/* ", " */", "'", "\"{", "\r\n", "\r", "-1", "$"];
+            let mut source = String::new();
+            for (index, fragment) in picks {
+                let line = index.get(lines);
+                source.push_str(fragments[fragment]);
+                source.push_str(line);
+            }
+            assert_lossless(&source);
+        }
+    }
+
+    /// tokens moved to the first file, so tokens of different scans can be compared
+    fn located(tokens: &[Token]) -> Vec<Token> {
+        return tokens
+            .iter()
+            .map(|t| {
+                let kind = match t.kind() {
+                    TokenType::LiteralTemplate(segments) => TokenType::LiteralTemplate(
+                        segments
+                            .iter()
+                            .map(|segment| match segment {
+                                TemplateSegment::Placeholder(tokens) => {
+                                    TemplateSegment::Placeholder(located(tokens))
+                                }
+                                text => text.clone(),
+                            })
+                            .collect(),
+                    ),
+                    kind => kind.clone(),
+                };
+                let span = Span {
+                    file: FileId(0),
+                    ..t.span()
+                };
+                Token::new(kind, span, t.pos())
+            })
+            .collect();
+    }
+
+    /// Re-lex a source after an edit, asserting the result is the same as scanning the edited source
+    fn assert_relex(lexer: &mut Lexer, source: &str, edit: Edit) {
+        let tokens = lexer.scan_str(source).unwrap();
+        let file = lexer.diagnostics().first().map_or(FileId(0), |e| e.file());
+        let file = tokens.first().map_or(file, |t| t.file());
+        let edited = edit.apply(source).unwrap();
+        let relexed = lexer.relex(file, &tokens, &edit).unwrap();
+        assert_eq!(lexer.source_map(file).unwrap().text(), edited);
+        assert_eq!(
+            located(&relexed),
+            located(&lexer.scan_str(&edited).unwrap())
+        );
+
+        // the re-lexed stream can be edited again
+        let edit = Edit::new(0..0, "\n");
+        let relexed = lexer.relex(file, &relexed, &edit).unwrap();
+        let expected = lexer.scan_str(&edit.apply(&edited).unwrap()).unwrap();
+        assert_eq!(located(&relexed), located(&expected));
+    }
+
+    #[test]
+    fn relex() {
+        let source = "a = 1\nb:\n    c = 'x' /* d\n e */\n    f\ng\n";
+        let mut lexer = Lexer::new();
+        assert_relex(&mut lexer, source, Edit::new(4..5, "2 + 3"));
+        assert_relex(&mut lexer, source, Edit::new(0..6, ""));
+        assert_relex(&mut lexer, source, Edit::new(9..9, "\n    h\n"));
+        assert_relex(&mut lexer, source, Edit::new(23..25, "*/ /*"));
+        assert_relex(&mut lexer, source, Edit::new(31..32, ""));
+        assert_relex(
+            &mut lexer,
+            source,
+            Edit::new(source.len()..source.len(), "i"),
+        );
+
+        // tokens following the first synchronised line are reused
+        let tokens = lexer.scan_str(source).unwrap();
+        let relexed = lexer
+            .relex(tokens[0].file(), &tokens, &Edit::new(4..5, "10"))
+            .unwrap();
+        assert_eq!(
+            relexed[2].kind(),
+            &TokenType::LiteralInteger(Integer::I32(10))
+        );
+        assert_eq!(relexed[5].span().start, tokens[5].span().start + 1);
+    }
+
+    #[test]
+    fn relex_errors() {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.scan_str("a = 1\né = 2\n").unwrap();
+        let file = tokens[0].file();
+
+        let error = lexer
+            .relex(FileId(7), &tokens, &Edit::new(0..1, "b"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnknownFile);
+        assert_eq!(error.to_string(), "unknown file 7");
+
+        let error = lexer
+            .relex(file, &tokens[1..], &Edit::new(0..1, "b"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::StaleTokens(8, 7));
+
+        for (start, end) in [(4, 40), (7, 7), (3, 2)] {
+            let range = start..end;
+            let error = lexer
+                .relex(file, &tokens, &Edit::new(range.clone(), "b"))
+                .unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidEdit(range));
+        }
+
+        // the file can still be re-lexed after an error
+        let relexed = lexer.relex(file, &tokens, &Edit::new(0..1, "b")).unwrap();
+        assert_eq!(relexed[0].kind(), &TokenType::Identifier("b".to_string()));
+    }
+
+    proptest! {
+        #[test]
+        fn relex_examples(
+            example in any::<prop::sample::Index>(),
+            start in any::<prop::sample::Index>(),
+            len in 0..40usize,
+            text in "[a-z =:\\-'/*\t\n]{0,12}",
+            lossless in any::<bool>(),
+        ) {
+            static SOURCES: OnceLock<Vec<String>> = OnceLock::new();
+            let sources = SOURCES.get_or_init(examples);
+            let source = example.get(sources);
+            let mut start = start.index(source.len() + 1);
+            while !source.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = (start + len).min(source.len());
+            while !source.is_char_boundary(end) {
+                end += 1;
+            }
+
+            let mut lexer = Lexer::new();
+            lexer.set_recovery(true);
+            lexer.set_lossless(lossless);
+            assert_relex(&mut lexer, source, Edit::new(start..end, &text));
+        }
+    }
+
+    #[test]
+    fn identifiers() {
+        let tokens = scan("last_incremented = _count + été2 + 변수\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("last_incremented".to_string()),
+                TokenType::Equal,
+                TokenType::Identifier("_count".to_string()),
+                TokenType::Plus,
+                TokenType::Identifier("été2".to_string()),
+                TokenType::Plus,
+                TokenType::Identifier("변수".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        // identifiers are compared in NFC, a decomposed `é` is the same as a precomposed one
+        let tokens = scan("e\u{301}t\u{e9}\n").unwrap();
+        assert_eq!(
+            tokens[0],
+            TokenType::Identifier("\u{e9}t\u{e9}".to_string())
+        );
+
+        // `·` may continue an identifier, but not start one
+        let error = scan("a = ·b\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidKeyword("·b".to_string()));
+    }
+
+    #[test]
+    fn identifier_length() {
+        let name = "a".repeat(32);
+        let error = scan(&format!("x = {}\n", name)).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::IdentifierTooLong(name.clone(), 31)
+        );
+        assert_eq!(error.pos(), Some(Position { line: 0, col: 4 }));
+
+        let mut lexer = Lexer::new();
+        lexer.set_max_identifier_len(40);
+        let tokens = lexer.scan_str(&name).unwrap();
+        assert_eq!(tokens[0].kind(), &TokenType::Identifier(name));
+
+        // the limit counts the characters of the normalised identifier
+        lexer.set_max_identifier_len(1);
+        let tokens = lexer.scan_str("e\u{301}").unwrap();
+        assert_eq!(
+            tokens[0].kind(),
+            &TokenType::Identifier("\u{e9}".to_string())
+        );
+    }
+
+    #[test]
+    fn records() {
+        let mut lexer = Lexer::new();
+        let record = |text: &str| Record::Exact(token::KEYWORD_PRIORITY, text.to_string());
+        lexer
+            .add_record(record("pour"), Lexeme::Token(TokenType::For))
+            .unwrap();
+        let tokens = lexer.scan_str("pour pour2\n").unwrap();
+        assert_eq!(tokens[0].kind(), &TokenType::For);
+        assert_eq!(
+            tokens[1].kind(),
+            &TokenType::Identifier("pour2".to_string())
+        );
+
+        // a keyword may not be redefined at the same priority, but a higher priority overrides it
+        let error = lexer
+            .add_record(record("for"), Lexeme::Identifier)
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &dictionary::ErrorKind::Conflict(record("for"))
+        );
+        let tokens = lexer.scan_str("for\n").unwrap();
+        assert_eq!(tokens[0].kind(), &TokenType::For);
+        let override_for = Record::Exact(token::KEYWORD_PRIORITY + 1, "for".to_string());
+        lexer.add_record(override_for, Lexeme::Identifier).unwrap();
+        let tokens = lexer.scan_str("for\n").unwrap();
+        assert_eq!(tokens[0].kind(), &TokenType::Identifier("for".to_string()));
+
+        // non-ASCII characters of the same class share a symbol of the automaton, the text is checked instead
+        let mut dictionary = Dictionary::new();
+        dictionary.add_custom(record("\u{fc}ber"), 7).unwrap();
+        let tokens = Lexer::from_dictionary(dictionary)
+            .scan_str("\u{fc}ber \u{e4}ber\n")
+            .unwrap();
+        assert_eq!(tokens[0].kind(), &TokenType::Custom(7));
+        assert_eq!(
+            tokens[1].kind(),
+            &TokenType::Identifier("\u{e4}ber".to_string())
+        );
+
+        // custom ids stop at `u32::MAX`, which is reserved
+        let mut dictionary = Dictionary::new();
+        let error = dictionary.add_custom(record("max"), u32::MAX);
+        assert_eq!(error, Err(ExtensionError::Exhausted));
+        assert_eq!(
+            dictionary.get("max"),
+            Some(TokenType::Identifier("max".to_string()))
+        );
+        dictionary.add_custom(record("last"), u32::MAX - 1).unwrap();
+        let error = dictionary.add_extension("keywords:\n  A_TWEEN: tween\n");
+        assert_eq!(error, Err(ExtensionError::Exhausted));
+        assert_eq!(
+            dictionary.get("last"),
+            Some(TokenType::Custom(u32::MAX - 1))
+        );
+    }
+
+    #[test]
+    fn extensions() {
+        let extension = r#"
+operators:
+  OP_PIPE: "|>"
+keywords:
+  TW_TWEEN: tween
+  TW_SPAWN: spawn
+patterns:
+  TW_ASSET: "@[a-z]+"
+line_comments:
+  LUA_LINE: "--"
+block_comments:
+  LUA_BLOCK: "--[[ ]]"
+strings:
+  BACKTICK: "`"
+"#;
+        let mut dictionary = Dictionary::new();
+        let ids = dictionary.add_extension(extension).unwrap();
+        assert_eq!(ids["OP_PIPE"], 0);
+        assert_eq!(ids["TW_ASSET"], 3);
+        let mut lexer = Lexer::from_dictionary(dictionary);
+        let tokens = lexer
+            .scan_str("spawn @ship |> tween `a` --[[ x\n]] -- y\nspawner\n")
+            .unwrap();
+        let kinds: Vec<TokenType> = tokens.iter().map(|t| t.kind().clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenType::Custom(ids["TW_SPAWN"]),
+                TokenType::Custom(ids["TW_ASSET"]),
+                TokenType::Custom(ids["OP_PIPE"]),
+                TokenType::Custom(ids["TW_TWEEN"]),
+                TokenType::LiteralString("a".to_string()),
+                TokenType::Comment(" x\n".to_string()),
+                TokenType::Comment(" y".to_string()),
+                TokenType::Newline,
+                TokenType::Identifier("spawner".to_string()),
+                TokenType::Newline,
+            ]
+        );
+        assert_eq!(tokens[1].span().start, 6);
+        assert_eq!(tokens[1].span().end, 11);
+
+        // extensions may not redefine keywords, and nothing is added by an invalid extension
+        let mut dictionary = Dictionary::new();
+        let error = dictionary
+            .add_extension("keywords:\n  A_TWEEN: tween\n  A_FOR: for\n")
+            .unwrap_err();
+        assert!(matches!(error, ExtensionError::Record(_)));
+        assert_eq!(
+            dictionary.get("tween"),
+            Some(TokenType::Identifier("tween".to_string()))
+        );
+        let error = dictionary.add_extension("strings:\n  QUOTES: '<>'\n");
+        assert!(matches!(error, Err(ExtensionError::Invalid(_))));
+        let error = dictionary.load_extension("missing.yaml");
+        assert!(matches!(error, Err(ExtensionError::Io(_))));
+    }
+
+    #[test]
+    fn radix_integers() {
+        let tokens = scan("mask = 0xFF_FFu + 0b1010_0101 * -0o755\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("mask".to_string()),
+                TokenType::Equal,
+                TokenType::LiteralInteger(Integer::U32(0xFFFF)),
+                TokenType::Plus,
+                TokenType::LiteralInteger(Integer::I32(0b1010_0101)),
+                TokenType::Asterisk,
+                TokenType::LiteralInteger(Integer::I32(-0o755)),
+                TokenType::Newline,
+            ]
+        );
+
+        let tokens = scan("1_000_000 + 1_000ms\n").unwrap();
+        assert_eq!(
+            tokens[0],
+            TokenType::LiteralInteger(Integer::I32(1_000_000))
+        );
+        assert_eq!(tokens[2], TokenType::LiteralDuration(1_000_000_000));
+
+        let error = scan("a = 1__0\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Literal(LiteralError::InvalidSeparator("1__0".to_string()))
+        );
+    }
+
+    #[test]
+    fn colors() {
+        let tokens = scan("color: #FF0000 # red\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("color".to_string()),
+                TokenType::Colon,
+                TokenType::LiteralColor(Color {
+                    r: 255,
+                    g: 0,
+                    b: 0,
+                    a: 255
+                }),
+                TokenType::Comment(" red".to_string()),
+                TokenType::Newline,
+            ]
+        );
+
+        let tokens = scan("tint = (#00ff0080, #0000FF)\n").unwrap();
+        assert_eq!(
+            tokens[3],
+            TokenType::LiteralColor(Color {
+                r: 0,
+                g: 255,
+                b: 0,
+                a: 128
+            })
+        );
+
+        // `#` remains a comment at the start of a line, after an operand and when not followed by a whole
+        // word of 6 or 8 hexadecimal digits
+        let tokens = scan("#facade\na #abcdef\nb = #abc\nc = #abcdefab1\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Comment("facade".to_string()),
+                TokenType::Identifier("a".to_string()),
+                TokenType::Comment("abcdef".to_string()),
+                TokenType::Newline,
+                TokenType::Identifier("b".to_string()),
+                TokenType::Equal,
+                TokenType::Comment("abc".to_string()),
+                TokenType::Newline,
+                TokenType::Identifier("c".to_string()),
+                TokenType::Equal,
+                TokenType::Comment("abcdefab1".to_string()),
+                TokenType::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn matrices() {
+        let int = |v| TokenType::LiteralInteger(Integer::I32(v));
+        let tokens = scan("a: (1 -2 3; 4 5  6)\n").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Identifier("a".to_string()),
+                TokenType::Colon,
+                TokenType::MatrixOpen,
+                int(1),
+                int(-2),
+                int(3),
+                TokenType::Semicolon,
+                int(4),
+                int(5),
+                int(6),
+                TokenType::ParanthesisClose,
+                TokenType::Newline,
+            ]
+        );
+
+        let tokens = Lexer::new().scan_str("c: v(1f 2f 3f)").unwrap();
+        let f = |v| TokenType::LiteralFloat(Float::F32(v));
+        assert_eq!(
+            tokens,
+            vec![
+                token(TokenType::Identifier("c".to_string()), 0, 1),
+                token(TokenType::Colon, 1, 1),
+                token(TokenType::VectorOpen, 3, 2),
+                token(f(1.0), 5, 2),
+                token(f(2.0), 8, 2),
+                token(f(3.0), 11, 2),
+                token(TokenType::ParanthesisClose, 13, 1),
+                token(TokenType::Newline, 14, 0),
+            ]
+        );
+
+        // calls, groups and expressions are left alone
+        let tokens = scan("v(1) + (1 - 2) + f(a b) + (3)\n").unwrap();
+        assert!(!tokens.contains(&TokenType::MatrixOpen));
+        assert!(!tokens.contains(&TokenType::VectorOpen));
+        for source in [
+            "y: f(1 2)\n",
+            "y: f(3 -1)\n",
+            "y: a.b(1 2) + g()(1 2) + m[0](1 2)\n",
+            "y: a.v(1 2)\n",
+        ] {
+            let tokens = scan(source).unwrap();
+            assert!(!tokens.contains(&TokenType::MatrixOpen), "{}", source);
+            assert!(!tokens.contains(&TokenType::VectorOpen), "{}", source);
+        }
+        let call = scan("y: f(3 -1)\n").unwrap();
+        assert_eq!(
+            &call[3..8],
+            &[
+                TokenType::ParanthesisOpen,
+                int(3),
+                TokenType::Minus,
+                int(1),
+                TokenType::ParanthesisClose
+            ]
+        );
+        assert_eq!(scan("y: f (1 2)\n").unwrap()[3], TokenType::MatrixOpen);
+        assert_eq!(scan("y: f(v(1 2))\n").unwrap()[4], TokenType::VectorOpen);
+        assert_eq!(tokens[0], TokenType::Identifier("v".to_string()));
+        assert_eq!(tokens[1], TokenType::ParanthesisOpen);
+
+        let error = scan("a: (1 2; 3)\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MatrixRowLength(2, 1));
+        assert_eq!(error.pos(), Some(Position { line: 0, col: 3 }));
+        assert_eq!(error.span().unwrap().len(), 8);
+
+        let error = scan("a: v(1 2; 3 4)\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::VectorRows);
+
+        let mut lexer = Lexer::new();
+        lexer.set_recovery(true);
+        let tokens = lexer.scan_str("a: (1 2; 3) + 1\n").unwrap();
+        assert_eq!(tokens[2].kind(), &TokenType::Error("(1 2; 3)".to_string()));
+        assert_eq!(tokens[3].kind(), &TokenType::Plus);
+    }
 }
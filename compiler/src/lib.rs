@@ -0,0 +1,11 @@
+pub mod ast;
+pub mod collections;
+pub mod dictionary;
+pub mod fsm;
+pub mod lexer;
+pub mod literal;
+pub mod parser;
+pub mod scanner;
+pub mod source;
+mod spec;
+pub mod token;
@@ -0,0 +1,732 @@
+use std::fmt;
+
+use crate::token::{Color, Float, Integer, ScalarType, TokenType};
+
+/// Error found while parsing the value of a literal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralError {
+    Overflow(String, ScalarType),
+    InvalidSuffix(String),
+    InvalidSeparator(String),
+    MissingDigits(String),
+    UnknownDurationUnit(String, String),
+    MissingDurationUnit(String),
+    /// Durations are unsigned, such as `-1s`
+    NegativeDuration(String),
+    InvalidEscape(String),
+    UnterminatedPlaceholder(String),
+    UnmatchedBrace(String),
+}
+
+impl fmt::Display for LiteralError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LiteralError::Overflow(value, scalar) => {
+                write!(f, "{} is out of the {} boundary", value, scalar)
+            }
+            LiteralError::InvalidSuffix(value) => {
+                write!(f, "invalid numeric suffix: {}", value)
+            }
+            LiteralError::InvalidSeparator(value) => {
+                write!(f, "`_` must be placed between two digits: {}", value)
+            }
+            LiteralError::MissingDigits(value) => {
+                write!(f, "missing digits after the radix prefix: {}", value)
+            }
+            LiteralError::UnknownDurationUnit(value, unit) => {
+                write!(
+                    f,
+                    "unknown unit {} in duration {}, expected one of ns, us, ms, s, m, h, d",
+                    unit, value
+                )
+            }
+            LiteralError::MissingDurationUnit(value) => {
+                write!(f, "missing unit in duration {}", value)
+            }
+            LiteralError::NegativeDuration(value) => {
+                write!(f, "durations cannot be negative: {}", value)
+            }
+            LiteralError::InvalidEscape(escape) => {
+                write!(f, "invalid escape sequence: {}", escape)
+            }
+            LiteralError::UnterminatedPlaceholder(value) => {
+                write!(f, "unterminated placeholder in string: {}", value)
+            }
+            LiteralError::UnmatchedBrace(value) => {
+                write!(
+                    f,
+                    "unmatched `}}` in string, use `}}}}` for a literal brace: {}",
+                    value
+                )
+            }
+        }
+    }
+}
+
+/// Parse a numeric literal with an optional sign and type suffix, such as `12`, `-34l`, `12ul` or `23.35f`
+///
+/// Integers without a suffix are inferred as `i32`, decimals without a suffix are inferred as `f32`. Any other
+/// suffix containing a duration unit is parsed as a duration, see `parse_duration`. Digits may be separated by
+/// `_`, and integers may be written in hexadecimal, binary or octal with a `0x`, `0b` or `0o` prefix.
+pub fn parse_number(value: &str) -> Result<TokenType, LiteralError> {
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let radix = match unsigned.get(..2) {
+        Some("0x") => 16,
+        Some("0b") => 2,
+        Some("0o") => 8,
+        _ => 10,
+    };
+    if radix != 10 {
+        return parse_radix_integer(value, radix);
+    }
+
+    let value = &strip_separators(value, 10)?;
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let digits_len = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(unsigned.len());
+    let (digits, suffix) = unsigned.split_at(digits_len);
+    let number = &value[..value.len() - suffix.len()];
+
+    if digits.is_empty()
+        || digits.starts_with('.')
+        || digits.ends_with('.')
+        || digits.matches('.').count() > 1
+    {
+        return Err(LiteralError::InvalidSuffix(value.to_string()));
+    }
+
+    if digits.contains('.') {
+        return match suffix {
+            "" | "f" => parse_float(number, ScalarType::F32),
+            "d" => parse_float(number, ScalarType::F64),
+            _ => parse_suffixed_duration(value, suffix),
+        };
+    }
+
+    return match suffix {
+        "" => parse_integer(number, ScalarType::I32),
+        "l" => parse_integer(number, ScalarType::I64),
+        "u" => parse_integer(number, ScalarType::U32),
+        "ul" => parse_integer(number, ScalarType::U64),
+        "f" => parse_float(number, ScalarType::F32),
+        "d" => parse_float(number, ScalarType::F64),
+        _ => parse_suffixed_duration(value, suffix),
+    };
+}
+
+/// Parse an integer literal with a `0x`, `0b` or `0o` prefix, the digits are only followed by an integer suffix
+fn parse_radix_integer(value: &str, radix: u32) -> Result<TokenType, LiteralError> {
+    let negative = value.starts_with('-');
+    let digits = &value[value.find('0').unwrap() + 2..];
+    let digits_len = digits
+        .find(|c: char| !c.is_digit(radix) && c != '_')
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(digits_len);
+    let number = &value[..value.len() - suffix.len()];
+    if digits.is_empty() {
+        return Err(LiteralError::MissingDigits(value.to_string()));
+    }
+    let digits = strip_separators(digits, radix)
+        .map_err(|_| LiteralError::InvalidSeparator(value.to_string()))?;
+
+    let scalar = match suffix {
+        "" => ScalarType::I32,
+        "l" => ScalarType::I64,
+        "u" => ScalarType::U32,
+        "ul" => ScalarType::U64,
+        _ => return Err(LiteralError::InvalidSuffix(value.to_string())),
+    };
+    let overflow = || LiteralError::Overflow(number.to_string(), scalar);
+    let magnitude = i128::from_str_radix(&digits, radix).map_err(|_| overflow())?;
+    let value = match negative {
+        true => -magnitude,
+        false => magnitude,
+    };
+    return integer(value, scalar).ok_or_else(overflow);
+}
+
+/// Remove the `_` separators of a numeric literal, which are only allowed between two digits of the radix
+fn strip_separators(value: &str, radix: u32) -> Result<String, LiteralError> {
+    let chars: Vec<char> = value.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        let is_digit = |j: Option<usize>| {
+            j.and_then(|j| chars.get(j))
+                .is_some_and(|c| c.is_digit(radix))
+        };
+        if *c == '_' && !(is_digit(i.checked_sub(1)) && is_digit(Some(i + 1))) {
+            return Err(LiteralError::InvalidSeparator(value.to_string()));
+        }
+    }
+    return Ok(value.replace('_', ""));
+}
+
+/// Parse the hexadecimal digits of a `#RRGGBB` or `#RRGGBBAA` colour literal, excluding the `#`
+pub fn parse_color(digits: &str) -> Option<Color> {
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(digits.get(i..i + 2).unwrap_or("FF"), 16).ok();
+    return Some(Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: channel(6)?,
+    });
+}
+
+/// Duration units and their length in nanoseconds
+const DURATION_UNITS: [(&str, u64); 8] = [
+    ("ns", 1),
+    ("us", 1_000),
+    ("µs", 1_000),
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("m", 60_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("d", 86_400_000_000_000),
+];
+
+/// Parse a Go-style duration string such as `1.5s`, `20.32ms` or `1h20s` into nanoseconds
+///
+/// A lone `d` suffix is reserved for 64-bit floats (`12d`), days are only recognised within a compound duration
+/// such as `1d12h`.
+pub fn parse_duration(value: &str) -> Result<TokenType, LiteralError> {
+    let overflow = || LiteralError::Overflow(value.to_string(), ScalarType::Duration);
+    if value.starts_with('-') {
+        return Err(LiteralError::NegativeDuration(value.to_string()));
+    }
+
+    let mut total: u128 = 0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() && fraction.is_empty() || fraction.contains('.') {
+            return Err(LiteralError::InvalidSuffix(value.to_string()));
+        }
+        if unit.is_empty() {
+            return Err(LiteralError::MissingDurationUnit(value.to_string()));
+        }
+        let scale = match DURATION_UNITS.iter().find(|(name, _)| *name == unit) {
+            Some((_, scale)) => *scale as u128,
+            None => {
+                return Err(LiteralError::UnknownDurationUnit(
+                    value.to_string(),
+                    unit.to_string(),
+                ))
+            }
+        };
+
+        // the sub-nanosecond precision of long fractions is dropped
+        let fraction = &fraction[..fraction.len().min(18)];
+        let whole: u128 = match whole {
+            "" => 0,
+            _ => whole.parse().map_err(|_| overflow())?,
+        };
+        let fraction_value: u128 = match fraction {
+            "" => 0,
+            _ => fraction.parse().map_err(|_| overflow())?,
+        };
+        total = whole
+            .checked_mul(scale)
+            .and_then(|v| v.checked_add(fraction_value * scale / 10u128.pow(fraction.len() as u32)))
+            .and_then(|v| v.checked_add(total))
+            .ok_or_else(overflow)?;
+        rest = tail;
+    }
+
+    return match u64::try_from(total) {
+        Ok(nanoseconds) => Ok(TokenType::LiteralDuration(nanoseconds)),
+        Err(_) => Err(overflow()),
+    };
+}
+
+/// Parse a number with an unknown numeric suffix as a duration, a suffix without any duration unit is reported
+/// as an invalid numeric suffix instead
+fn parse_suffixed_duration(value: &str, suffix: &str) -> Result<TokenType, LiteralError> {
+    let is_unit = |unit: &str| DURATION_UNITS.iter().any(|(name, _)| *name == unit);
+    if suffix.chars().all(|c| c.is_alphabetic()) && !is_unit(suffix) {
+        return Err(LiteralError::InvalidSuffix(value.to_string()));
+    }
+    return parse_duration(value);
+}
+
+/// Parse the raw content of a quoted literal, excluding the quotes
+///
+/// Double quotes always produce a string. Single quotes produce a `char` when they contain exactly one
+/// character, longer single-quoted text is still accepted as a string.
+pub fn parse_quoted(quote: char, raw: &str) -> Result<TokenType, LiteralError> {
+    let value = unescape(raw)?;
+    if quote == '\'' {
+        let mut chars = value.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(TokenType::LiteralChar(c));
+        }
+    }
+    return Ok(TokenType::LiteralString(value));
+}
+
+/// Part of an interpolated string literal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplatePart<'a> {
+    /// Unescaped text between placeholders
+    Text(String),
+    /// Raw expression of a `{...}` placeholder and the char offset of its `{` within the literal, the expression
+    /// is empty for positional `{}` placeholders
+    Placeholder(usize, &'a str),
+}
+
+/// Split the raw content of a string literal into text and `{...}` placeholders, `{{` and `}}` are escaped braces
+pub fn split_template(raw: &str) -> Result<Vec<TemplatePart<'_>>, LiteralError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = raw.char_indices().enumerate().peekable();
+    while let Some((offset, (i, c))) = chars.next() {
+        let escaped = chars.peek().is_some_and(|(_, (_, next))| *next == c);
+        match c {
+            '{' | '}' if escaped => {
+                text.push(c);
+                chars.next();
+            }
+            '{' => {
+                let mut depth = 0;
+                let mut end = None;
+                for (_, (j, c)) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => {
+                            end = Some(j);
+                            break;
+                        }
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                }
+                let end =
+                    end.ok_or_else(|| LiteralError::UnterminatedPlaceholder(raw.to_string()))?;
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(unescape(&text)?));
+                    text.clear();
+                }
+                parts.push(TemplatePart::Placeholder(offset, &raw[i + 1..end]));
+            }
+            '}' => return Err(LiteralError::UnmatchedBrace(raw.to_string())),
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(unescape(&text)?));
+    }
+    return Ok(parts);
+}
+
+/// Resolve the escape sequences `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'` and `\u{...}` of a quoted literal
+pub fn unescape(raw: &str) -> Result<String, LiteralError> {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => unescape_unicode(&mut chars)?,
+            Some(other) => return Err(LiteralError::InvalidEscape(format!("\\{}", other))),
+            None => return Err(LiteralError::InvalidEscape("\\".to_string())),
+        };
+        value.push(escaped);
+    }
+    return Ok(value);
+}
+
+/// Resolve the `{...}` part of a `\u{...}` escape sequence into a unicode scalar value
+fn unescape_unicode(chars: &mut std::str::Chars) -> Result<char, LiteralError> {
+    let mut sequence = String::from("\\u");
+    if chars.next() != Some('{') {
+        return Err(LiteralError::InvalidEscape(sequence));
+    }
+    sequence.push('{');
+    for c in chars.by_ref() {
+        sequence.push(c);
+        if c == '}' {
+            let digits = &sequence[3..sequence.len() - 1];
+            return u32::from_str_radix(digits, 16)
+                .ok()
+                .filter(|_| !digits.is_empty() && digits.len() <= 6)
+                .and_then(char::from_u32)
+                .ok_or(LiteralError::InvalidEscape(sequence));
+        }
+    }
+    return Err(LiteralError::InvalidEscape(sequence));
+}
+
+fn parse_integer(number: &str, scalar: ScalarType) -> Result<TokenType, LiteralError> {
+    let overflow = || LiteralError::Overflow(number.to_string(), scalar);
+    let value: i128 = number.parse().map_err(|_| overflow())?;
+    return integer(value, scalar).ok_or_else(overflow);
+}
+
+/// Returns the integer literal of a value, or none if the value is out of the boundary of the scalar type
+fn integer(value: i128, scalar: ScalarType) -> Option<TokenType> {
+    let integer = match scalar {
+        ScalarType::I32 => Integer::I32(value.try_into().ok()?),
+        ScalarType::I64 => Integer::I64(value.try_into().ok()?),
+        ScalarType::U32 => Integer::U32(value.try_into().ok()?),
+        _ => Integer::U64(value.try_into().ok()?),
+    };
+    return Some(TokenType::LiteralInteger(integer));
+}
+
+fn parse_float(number: &str, scalar: ScalarType) -> Result<TokenType, LiteralError> {
+    let overflow = || LiteralError::Overflow(number.to_string(), scalar);
+    let float = match scalar {
+        ScalarType::F64 => Float::F64(number.parse().map_err(|_| overflow())?),
+        _ => Float::F32(number.parse().map_err(|_| overflow())?),
+    };
+    let finite = match float {
+        Float::F32(v) => v.is_finite(),
+        Float::F64(v) => v.is_finite(),
+    };
+    if !finite {
+        return Err(overflow());
+    }
+    return Ok(TokenType::LiteralFloat(float));
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_color, parse_duration, parse_number, parse_quoted, split_template, LiteralError,
+        TemplatePart,
+    };
+    use crate::token::{Color, Float, Integer, ScalarType, TokenType};
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            parse_number("12"),
+            Ok(TokenType::LiteralInteger(Integer::I32(12)))
+        );
+        assert_eq!(
+            parse_number("-34"),
+            Ok(TokenType::LiteralInteger(Integer::I32(-34)))
+        );
+        assert_eq!(
+            parse_number("12l"),
+            Ok(TokenType::LiteralInteger(Integer::I64(12)))
+        );
+        assert_eq!(
+            parse_number("34345u"),
+            Ok(TokenType::LiteralInteger(Integer::U32(34345)))
+        );
+        assert_eq!(
+            parse_number("12ul"),
+            Ok(TokenType::LiteralInteger(Integer::U64(12)))
+        );
+        assert_eq!(
+            parse_number("-2147483648"),
+            Ok(TokenType::LiteralInteger(Integer::I32(i32::MIN)))
+        );
+    }
+
+    #[test]
+    fn radix_integers() {
+        assert_eq!(
+            parse_number("0xFF"),
+            Ok(TokenType::LiteralInteger(Integer::I32(255)))
+        );
+        assert_eq!(
+            parse_number("-0b1010_0101l"),
+            Ok(TokenType::LiteralInteger(Integer::I64(-0b1010_0101)))
+        );
+        assert_eq!(
+            parse_number("0o755u"),
+            Ok(TokenType::LiteralInteger(Integer::U32(0o755)))
+        );
+        assert_eq!(
+            parse_number("0xFFFF_FFFFu"),
+            Ok(TokenType::LiteralInteger(Integer::U32(u32::MAX)))
+        );
+        assert_eq!(
+            parse_number("0xFFFFFFFF"),
+            Err(LiteralError::Overflow(
+                "0xFFFFFFFF".to_string(),
+                ScalarType::I32
+            ))
+        );
+        assert_eq!(
+            parse_number("0x"),
+            Err(LiteralError::MissingDigits("0x".to_string()))
+        );
+        assert_eq!(
+            parse_number("0b102"),
+            Err(LiteralError::InvalidSuffix("0b102".to_string()))
+        );
+        assert_eq!(
+            parse_number("0x1.5"),
+            Err(LiteralError::InvalidSuffix("0x1.5".to_string()))
+        );
+    }
+
+    #[test]
+    fn separators() {
+        assert_eq!(
+            parse_number("1_000_000"),
+            Ok(TokenType::LiteralInteger(Integer::I32(1_000_000)))
+        );
+        assert_eq!(
+            parse_number("1_000.5_5d"),
+            Ok(TokenType::LiteralFloat(Float::F64(1_000.55)))
+        );
+        for value in ["1_", "1__0", "1_.5", "1._5", "1_s", "0x_FF", "0xFF_"] {
+            assert_eq!(
+                parse_number(value),
+                Err(LiteralError::InvalidSeparator(value.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            parse_color("FF8000"),
+            Some(Color {
+                r: 255,
+                g: 128,
+                b: 0,
+                a: 255
+            })
+        );
+        assert_eq!(
+            parse_color("ff800040"),
+            Some(Color {
+                r: 255,
+                g: 128,
+                b: 0,
+                a: 64
+            })
+        );
+        assert_eq!(parse_color("FF80"), None);
+        assert_eq!(parse_color("FF800"), None);
+        assert_eq!(parse_color("GG8000"), None);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            parse_number("23.35f"),
+            Ok(TokenType::LiteralFloat(Float::F32(23.35)))
+        );
+        assert_eq!(
+            parse_number("-346.34d"),
+            Ok(TokenType::LiteralFloat(Float::F64(-346.34)))
+        );
+        assert_eq!(
+            parse_number("0f"),
+            Ok(TokenType::LiteralFloat(Float::F32(0.0)))
+        );
+        assert_eq!(
+            parse_number("12d"),
+            Ok(TokenType::LiteralFloat(Float::F64(12.0)))
+        );
+        assert_eq!(
+            parse_number("13.0"),
+            Ok(TokenType::LiteralFloat(Float::F32(13.0)))
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(
+            parse_number("2147483648"),
+            Err(LiteralError::Overflow(
+                "2147483648".to_string(),
+                ScalarType::I32
+            ))
+        );
+        assert_eq!(
+            parse_number("-1u"),
+            Err(LiteralError::Overflow("-1".to_string(), ScalarType::U32))
+        );
+        assert_eq!(
+            parse_number("18446744073709551616ul"),
+            Err(LiteralError::Overflow(
+                "18446744073709551616".to_string(),
+                ScalarType::U64
+            ))
+        );
+        assert!(parse_number("18446744073709551615ul").is_ok());
+        assert!(parse_number("9223372036854775808l").is_err());
+    }
+
+    #[test]
+    fn invalid_suffix() {
+        assert_eq!(
+            parse_number("12x"),
+            Err(LiteralError::InvalidSuffix("12x".to_string()))
+        );
+        assert_eq!(
+            parse_number("1.5u"),
+            Err(LiteralError::InvalidSuffix("1.5u".to_string()))
+        );
+        assert_eq!(
+            parse_number("1.2.3"),
+            Err(LiteralError::InvalidSuffix("1.2.3".to_string()))
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(
+            parse_number("1s"),
+            Ok(TokenType::LiteralDuration(1_000_000_000))
+        );
+        assert_eq!(
+            parse_number("1.5s"),
+            Ok(TokenType::LiteralDuration(1_500_000_000))
+        );
+        assert_eq!(
+            parse_number("20.32ms"),
+            Ok(TokenType::LiteralDuration(20_320_000))
+        );
+        assert_eq!(
+            parse_number("1h20s"),
+            Ok(TokenType::LiteralDuration(3_620_000_000_000))
+        );
+        assert_eq!(
+            parse_number("1d12h"),
+            Ok(TokenType::LiteralDuration(129_600_000_000_000))
+        );
+        assert_eq!(
+            parse_number("300µs"),
+            Ok(TokenType::LiteralDuration(300_000))
+        );
+        assert_eq!(
+            parse_duration("10d"),
+            Ok(TokenType::LiteralDuration(864_000_000_000_000))
+        );
+        assert_eq!(
+            parse_number("10d"),
+            Ok(TokenType::LiteralFloat(Float::F64(10.0)))
+        );
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert_eq!(
+            parse_number("1h20x"),
+            Err(LiteralError::UnknownDurationUnit(
+                "1h20x".to_string(),
+                "x".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_number("1h20"),
+            Err(LiteralError::MissingDurationUnit("1h20".to_string()))
+        );
+        assert_eq!(
+            parse_duration("213504d"),
+            Err(LiteralError::Overflow(
+                "213504d".to_string(),
+                ScalarType::Duration
+            ))
+        );
+        assert!(parse_duration("213503d").is_ok());
+        assert_eq!(
+            parse_number("-1s"),
+            Err(LiteralError::NegativeDuration("-1s".to_string()))
+        );
+        assert_eq!(
+            LiteralError::NegativeDuration("-1s".to_string()).to_string(),
+            "durations cannot be negative: -1s"
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            parse_quoted('"', r#"a\tb\n\\ \"c\" \u{1F600}"#),
+            Ok(TokenType::LiteralString(
+                "a\tb\n\\ \"c\" \u{1F600}".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_quoted('\'', "Hello, World!"),
+            Ok(TokenType::LiteralString("Hello, World!".to_string()))
+        );
+        assert_eq!(
+            parse_quoted('"', "a"),
+            Ok(TokenType::LiteralString("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn chars() {
+        assert_eq!(parse_quoted('\'', "a"), Ok(TokenType::LiteralChar('a')));
+        assert_eq!(parse_quoted('\'', r"\'"), Ok(TokenType::LiteralChar('\'')));
+        assert_eq!(
+            parse_quoted('\'', r"\u{e9}"),
+            Ok(TokenType::LiteralChar('é'))
+        );
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        assert_eq!(
+            parse_quoted('"', r"\q"),
+            Err(LiteralError::InvalidEscape(r"\q".to_string()))
+        );
+        assert_eq!(
+            parse_quoted('"', r"\u{110000}"),
+            Err(LiteralError::InvalidEscape(r"\u{110000}".to_string()))
+        );
+        assert_eq!(
+            parse_quoted('"', r"\u{41"),
+            Err(LiteralError::InvalidEscape(r"\u{41".to_string()))
+        );
+    }
+
+    #[test]
+    fn templates() {
+        assert_eq!(
+            split_template("{event.sender} says {event.message}"),
+            Ok(vec![
+                TemplatePart::Placeholder(0, "event.sender"),
+                TemplatePart::Text(" says ".to_string()),
+                TemplatePart::Placeholder(20, "event.message"),
+            ])
+        );
+        assert_eq!(
+            split_template(r"Counter {} \t{{incremented}}"),
+            Ok(vec![
+                TemplatePart::Text("Counter ".to_string()),
+                TemplatePart::Placeholder(8, ""),
+                TemplatePart::Text(" \t{incremented}".to_string()),
+            ])
+        );
+        assert_eq!(
+            split_template("a {b"),
+            Err(LiteralError::UnterminatedPlaceholder("a {b".to_string()))
+        );
+        assert_eq!(
+            split_template("a } b"),
+            Err(LiteralError::UnmatchedBrace("a } b".to_string()))
+        );
+    }
+}
@@ -1,10 +1,4 @@
-use crate::lexer::Lexer;
-use std;
-
-mod collections;
-mod dictionary;
-mod lexer;
-mod token;
+use ecsl_compiler::lexer::Lexer;
 
 fn main() {
     let mut lexer = Lexer::new();
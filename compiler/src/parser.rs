@@ -0,0 +1,2017 @@
+//! Recursive-descent parser turning the tokens of a file into the declarations of a module, see `ast`.
+
+use std::fmt;
+
+use crate::ast::{
+    Access, AccessMode, AccessTarget, AssignOperator, BinaryOperator, Block, Declaration,
+    DeclarationKind, Dependency, Expr, ExprKind, Field, FunctionDecl, HandlerDecl, HandlerKind,
+    Identifier, Lambda, LambdaBody, Literal, Module, OrderConstraint, Ordering, Parameter,
+    RecordDecl, RecordKind, Statement, StatementKind, SystemDecl, TypeExpr, TypeKind,
+    UnaryOperator, VariableDecl,
+};
+use crate::token::{FileId, Span, Token, TokenType, CONTEXTUAL_KEYWORDS, KEYWORDS, OPERATORS};
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Span of the last consumed token which is not a line break or an indentation change, where the node being
+    /// parsed ends
+    previous: Span,
+    /// Empty span at the end of the file, where missing tokens are reported
+    end: Span,
+}
+
+impl Parser {
+    /// Create a parser over the tokens of a single file, comments and whitespace are skipped while doc comments
+    /// are attached to the declaration following them
+    pub fn new(tokens: Vec<Token>) -> Parser {
+        let end = match tokens.last() {
+            Some(token) => Span {
+                file: token.file(),
+                start: token.span().end,
+                end: token.span().end,
+            },
+            None => Span {
+                file: FileId(0),
+                start: 0,
+                end: 0,
+            },
+        };
+        let mut filtered: Vec<Token> = Vec::new();
+        for token in tokens {
+            match token.kind() {
+                TokenType::Comment(_) | TokenType::Whitespace(_) => (),
+                // the lexer changes the indentation at the first line which is not a comment, the doc comments
+                // preceding it are moved inside the new indentation level so they belong to the line they document
+                TokenType::Indent | TokenType::Dedent => {
+                    let docs = filtered
+                        .iter()
+                        .rev()
+                        .take_while(|t| matches!(t.kind(), TokenType::DocComment(_)))
+                        .count();
+                    filtered.insert(filtered.len() - docs, token);
+                }
+                _ => filtered.push(token),
+            }
+        }
+        return Parser {
+            tokens: filtered,
+            position: 0,
+            previous: Span { end: 0, ..end },
+            end,
+        };
+    }
+
+    pub fn parse_module(&mut self) -> Result<Module> {
+        let mut declarations = Vec::new();
+        while self.peek().is_some() {
+            declarations.push(self.parse_declaration()?);
+        }
+        return Ok(Module {
+            declarations,
+            span: Span {
+                start: 0,
+                ..self.end
+            },
+        });
+    }
+
+    fn parse_declaration(&mut self) -> Result<Declaration> {
+        let doc = self.parse_doc();
+        if doc.is_some() && self.peek().is_none() {
+            return Err(ParseError {
+                kind: ErrorKind::DanglingDocComment,
+                span: self.previous,
+            });
+        }
+
+        let start = self.peek_span();
+        let exposed = self.eat(&TokenType::Expose).is_some();
+        let kind = match self.peek_kind() {
+            Some(TokenType::Component) | Some(TokenType::Structure) => {
+                DeclarationKind::Record(self.parse_record()?)
+            }
+            // `state` and `event` are contextual keywords, `state.counter = 1` is a statement
+            Some(TokenType::State) | Some(TokenType::Event)
+                if self.peek_kind_at(1).and_then(|k| k.identifier()).is_some() =>
+            {
+                DeclarationKind::Record(self.parse_record()?)
+            }
+            Some(TokenType::Function) => DeclarationKind::Function(self.parse_function()?),
+            Some(TokenType::System) => DeclarationKind::System(self.parse_system()?),
+            // `handle` and `hook` are contextual keywords as well, `hook(x)` is a statement
+            Some(TokenType::Handle) | Some(TokenType::Hook)
+                if self.peek_kind_at(1).and_then(|k| k.identifier()).is_some() =>
+            {
+                DeclarationKind::Handler(self.parse_handler()?)
+            }
+            Some(TokenType::Const) => DeclarationKind::Variable(self.parse_constant()?),
+            _ if self.is_variable() => DeclarationKind::Variable(self.parse_variable()?),
+            _ => DeclarationKind::Statement(self.parse_statement()?),
+        };
+        return Ok(Declaration {
+            kind,
+            doc,
+            exposed,
+            span: start.to(self.previous),
+        });
+    }
+
+    /// Parse the doc comments preceding a line, joined by line breaks
+    fn parse_doc(&mut self) -> Option<String> {
+        let mut doc = Vec::new();
+        while let Some(TokenType::DocComment(text)) = self.peek_kind() {
+            doc.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+            self.advance();
+            self.eat(&TokenType::Newline);
+        }
+        return (!doc.is_empty()).then(|| doc.join("\n"));
+    }
+
+    /// Parse `component Name`, `state Name`, `structure Name` or `event Name`, followed by an indented field per
+    /// line
+    fn parse_record(&mut self) -> Result<RecordDecl> {
+        let (kind, expected) = match self.advance().map(|t| t.kind().clone()) {
+            Some(TokenType::Component) => (RecordKind::Component, "component name"),
+            Some(TokenType::State) => (RecordKind::State, "state name"),
+            Some(TokenType::Event) => (RecordKind::Event, "event name"),
+            _ => (RecordKind::Structure, "structure name"),
+        };
+        let name = self.expect_identifier(expected)?;
+        self.eat(&TokenType::Colon);
+        self.expect_line_end()?;
+        let mut fields = Vec::new();
+        if self.eat(&TokenType::Indent).is_some() {
+            while self.eat(&TokenType::Dedent).is_none() {
+                fields.push(self.parse_field()?);
+            }
+        }
+        return Ok(RecordDecl { kind, name, fields });
+    }
+
+    /// Parse a `name: Type`, `name Type` or `name ?: Type` field on its own line, optionally followed by
+    /// `optional` and by `default=value`
+    fn parse_field(&mut self) -> Result<Field> {
+        let doc = self.parse_doc();
+        let name = self.expect_identifier("field name")?;
+        let mut optional = self.eat(&TokenType::OptionalColon).is_some();
+        if !optional {
+            self.eat(&TokenType::Colon);
+        }
+        let ty = self.parse_type()?;
+        optional |= self.eat(&TokenType::Optional).is_some();
+        let default = match self.eat(&TokenType::Default) {
+            Some(_) => {
+                self.expect(&TokenType::Equal)?;
+                Some(self.parse_expression()?)
+            }
+            None => None,
+        };
+        let span = name.span.to(self.previous);
+        self.expect_line_end()?;
+        return Ok(Field {
+            name,
+            ty,
+            optional,
+            default,
+            doc,
+            span,
+        });
+    }
+
+    /// Parse `function Name` followed by a lambda, either on the same line or on an indented line
+    fn parse_function(&mut self) -> Result<FunctionDecl> {
+        self.expect(&TokenType::Function)?;
+        let name = self.expect_identifier("function name")?;
+        let indented = self.eat(&TokenType::Newline).is_some();
+        if indented {
+            self.expect(&TokenType::Indent)?;
+            self.parse_doc();
+        }
+        let lambda = self.parse_lambda()?;
+        self.expect_line_end()?;
+        if indented {
+            self.expect(&TokenType::Dedent)?;
+        }
+        return Ok(FunctionDecl { name, lambda });
+    }
+
+    /// Parse `system Name` followed by an indented block of clauses, one per line, ending with a lambda
+    fn parse_system(&mut self) -> Result<SystemDecl> {
+        self.expect(&TokenType::System)?;
+        let name = self.expect_identifier("system name")?;
+        self.expect(&TokenType::Newline)?;
+        if self.eat(&TokenType::Indent).is_none() {
+            return Err(ParseError {
+                kind: ErrorKind::ExpectedBlock,
+                span: self.peek_span(),
+            });
+        }
+
+        let mut accesses = Vec::new();
+        let mut filters = Vec::new();
+        let mut ordering = Vec::new();
+        let mut dependencies = Vec::new();
+        loop {
+            self.parse_doc();
+            let start = self.peek_span();
+            let mode = match self.peek_kind() {
+                Some(TokenType::Reads) => Some(AccessMode::Read),
+                Some(TokenType::Mutates) => Some(AccessMode::Write),
+                Some(TokenType::Without) => Some(AccessMode::Without),
+                Some(TokenType::On) => Some(AccessMode::On),
+                Some(TokenType::Foreach) => Some(AccessMode::Foreach),
+                _ => None,
+            };
+            let constraint = match self.peek_kind() {
+                Some(TokenType::After) => Some(OrderConstraint::After),
+                Some(TokenType::Before) => Some(OrderConstraint::Before),
+                _ => None,
+            };
+            if let Some(mode) = mode {
+                self.advance();
+                let target = match self.peek_kind() {
+                    Some(TokenType::Event) => AccessTarget::Event,
+                    Some(TokenType::State) => AccessTarget::State,
+                    _ => AccessTarget::Component,
+                };
+                if target != AccessTarget::Component {
+                    self.advance();
+                }
+                let path = self.parse_path("component, event or state name")?;
+                accesses.push(Access {
+                    mode,
+                    target,
+                    path,
+                    span: start.to(self.previous),
+                });
+            } else if let Some(constraint) = constraint {
+                self.advance();
+                let system = self.parse_path("system name")?;
+                ordering.push(Ordering {
+                    constraint,
+                    system,
+                    span: start.to(self.previous),
+                });
+            } else if self.eat(&TokenType::Filter).is_some() {
+                filters.push(self.parse_expression()?);
+            } else if self.eat(&TokenType::Depends).is_some() {
+                let name = self.expect_identifier("dependency name")?;
+                self.expect(&TokenType::BracketOpen)?;
+                let ty = self.parse_type()?;
+                self.expect(&TokenType::BracketClose)?;
+                dependencies.push(Dependency {
+                    name,
+                    ty,
+                    span: start.to(self.previous),
+                });
+            } else if self.check(&TokenType::ParanthesisOpen)
+                || self.peek_kind_at(1) == Some(&TokenType::Lambda)
+            {
+                break;
+            } else {
+                return Err(self.unexpected("system clause or lambda"));
+            }
+            self.expect_line_end()?;
+        }
+
+        let lambda = self.parse_lambda()?;
+        self.expect_line_end()?;
+        self.expect(&TokenType::Dedent)?;
+        return Ok(SystemDecl {
+            name,
+            accesses,
+            filters,
+            ordering,
+            dependencies,
+            lambda,
+        });
+    }
+
+    /// Parse `handle Event` or `hook Event`, where `event` may precede the name of the event, followed by a lambda
+    /// on the same line or by an indented block of `filter` clauses, one per line, ending with a lambda
+    fn parse_handler(&mut self) -> Result<HandlerDecl> {
+        let kind = match self.advance().map(|t| t.kind().clone()) {
+            Some(TokenType::Hook) => HandlerKind::Hook,
+            _ => HandlerKind::Handle,
+        };
+        if self.check(&TokenType::Event)
+            && self.peek_kind_at(1).and_then(|k| k.identifier()).is_some()
+        {
+            self.advance();
+        }
+        let event = self.parse_path("event name")?;
+        let indented = self.eat(&TokenType::Newline).is_some();
+        let mut filters = Vec::new();
+        if indented {
+            if self.eat(&TokenType::Indent).is_none() {
+                return Err(ParseError {
+                    kind: ErrorKind::ExpectedBlock,
+                    span: self.peek_span(),
+                });
+            }
+            self.parse_doc();
+            while self.eat(&TokenType::Filter).is_some() {
+                filters.push(self.parse_expression()?);
+                self.expect_line_end()?;
+                self.parse_doc();
+            }
+        }
+        let lambda = self.parse_lambda()?;
+        self.expect_line_end()?;
+        if indented {
+            self.expect(&TokenType::Dedent)?;
+        }
+        return Ok(HandlerDecl {
+            kind,
+            event,
+            filters,
+            lambda,
+        });
+    }
+
+    /// Parse `const name = value`, where `:` may be used in place of `=`
+    fn parse_constant(&mut self) -> Result<VariableDecl> {
+        self.expect(&TokenType::Const)?;
+        let name = self.expect_identifier("constant name")?;
+        if self.eat(&TokenType::Colon).is_none() {
+            self.expect(&TokenType::Equal)?;
+        }
+        let value = self.parse_expression()?;
+        self.expect_line_end()?;
+        return Ok(VariableDecl {
+            name,
+            constant: true,
+            mutable: false,
+            ty: None,
+            value,
+        });
+    }
+
+    /// Returns true if the line starts with `name:` or `name (...):`
+    fn is_variable(&self) -> bool {
+        if self.peek_kind().and_then(|k| k.identifier()).is_none() {
+            return false;
+        }
+        return match self.peek_kind_at(1) {
+            Some(TokenType::Colon) => true,
+            Some(TokenType::ParanthesisOpen) => {
+                let close = self.matching_close(1);
+                close.is_some_and(|i| self.peek_kind_at(i + 1) == Some(&TokenType::Colon))
+            }
+            _ => false,
+        };
+    }
+
+    /// Parse `name: value`, with either `(mutable)` or `(Type)` between the name and the colon
+    fn parse_variable(&mut self) -> Result<VariableDecl> {
+        let name = self.expect_identifier("variable name")?;
+        let mut mutable = false;
+        let mut ty = None;
+        if self.eat(&TokenType::ParanthesisOpen).is_some() {
+            let is_mutable =
+                matches!(self.peek_kind(), Some(TokenType::Identifier(m)) if m == "mutable");
+            if is_mutable && self.peek_kind_at(1) == Some(&TokenType::ParanthesisClose) {
+                self.advance();
+                mutable = true;
+            } else {
+                ty = Some(self.parse_type()?);
+            }
+            self.expect(&TokenType::ParanthesisClose)?;
+        }
+        self.expect(&TokenType::Colon)?;
+        let value = self.parse_expression()?;
+        self.expect_line_end()?;
+        return Ok(VariableDecl {
+            name,
+            constant: false,
+            mutable,
+            ty,
+            value,
+        });
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement> {
+        let start = self.peek_span();
+        let kind = match self.peek_kind() {
+            Some(TokenType::Indent) => {
+                self.advance();
+                return Err(ParseError {
+                    kind: ErrorKind::UnexpectedIndent,
+                    span: Span {
+                        end: self.peek_span().start,
+                        ..start
+                    },
+                });
+            }
+            Some(TokenType::If) => self.parse_if()?,
+            Some(TokenType::While) => {
+                self.advance();
+                let condition = self.parse_expression()?;
+                let body = self.parse_indented_block()?;
+                StatementKind::While { condition, body }
+            }
+            Some(TokenType::Return) => {
+                self.advance();
+                match self.at_line_end() {
+                    true => StatementKind::Return(None),
+                    false => StatementKind::Return(Some(self.parse_expression()?)),
+                }
+            }
+            Some(TokenType::Use) => {
+                self.advance();
+                StatementKind::Use(self.parse_path("module name")?)
+            }
+            Some(TokenType::Emit) => {
+                self.advance();
+                let event = self.parse_command(&[TokenType::To, TokenType::With])?;
+                let target = match self.eat(&TokenType::To) {
+                    Some(_) => Some(self.parse_expression()?),
+                    None => None,
+                };
+                let priority = match self.eat(&TokenType::With) {
+                    Some(_) => {
+                        self.expect(&TokenType::Priority)?;
+                        Some(Box::new(self.parse_expression()?))
+                    }
+                    None => None,
+                };
+                StatementKind::Emit {
+                    event,
+                    target,
+                    priority,
+                }
+            }
+            _ => {
+                let target = self.parse_command(&[])?;
+                let operator = match self.peek_kind() {
+                    Some(TokenType::Equal) => Some(AssignOperator::Assign),
+                    Some(TokenType::PlusEqual) => Some(AssignOperator::Add),
+                    Some(TokenType::MinusEqual) => Some(AssignOperator::Subtract),
+                    _ => None,
+                };
+                match operator {
+                    Some(operator) => {
+                        self.advance();
+                        let value = self.parse_expression()?;
+                        StatementKind::Assignment {
+                            target,
+                            operator,
+                            value,
+                        }
+                    }
+                    None => StatementKind::Expression(target),
+                }
+            }
+        };
+        let span = start.to(self.previous);
+        self.expect_line_end()?;
+        return Ok(Statement { kind, span });
+    }
+
+    /// Parse `if condition` followed by a block, and an optional `else` or `else if` on the line following the
+    /// block
+    fn parse_if(&mut self) -> Result<StatementKind> {
+        self.expect(&TokenType::If)?;
+        let condition = self.parse_expression()?;
+        let then = self.parse_indented_block()?;
+        let mut otherwise = None;
+        // doc comments between the block and `else` are skipped as in blocks
+        let mut offset = 1;
+        while let Some(TokenType::DocComment(_)) = self.peek_kind_at(offset) {
+            offset += 1;
+        }
+        if self.check(&TokenType::Dedent) && self.peek_kind_at(offset) == Some(&TokenType::Else) {
+            self.advance();
+            self.parse_doc();
+            self.advance();
+            otherwise = Some(match self.check(&TokenType::If) {
+                true => {
+                    let start = self.peek_span();
+                    let kind = self.parse_if()?;
+                    let span = start.to(self.previous);
+                    Block {
+                        statements: vec![Statement { kind, span }],
+                        span,
+                    }
+                }
+                false => self.parse_indented_block()?,
+            });
+        }
+        return Ok(StatementKind::If {
+            condition,
+            then,
+            otherwise,
+        });
+    }
+
+    /// Parse the end of a line followed by an indented block
+    fn parse_indented_block(&mut self) -> Result<Block> {
+        self.expect(&TokenType::Newline)?;
+        return self.parse_block();
+    }
+
+    /// Parse the statements of an indented block. The dedent closing the block is left to end the line which
+    /// opened it, see `expect_line_end`
+    fn parse_block(&mut self) -> Result<Block> {
+        let Some(indent) = self.eat(&TokenType::Indent) else {
+            return Err(ParseError {
+                kind: ErrorKind::ExpectedBlock,
+                span: self.peek_span(),
+            });
+        };
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::Dedent) && self.peek().is_some() {
+            // only declarations keep their documentation
+            if self.parse_doc().is_none() {
+                statements.push(self.parse_statement()?);
+            }
+        }
+        return Ok(Block {
+            span: indent.span().to(self.previous),
+            statements,
+        });
+    }
+
+    /// Parse an expression followed by the arguments of a call statement separated by whitespace, such as
+    /// `print "a = {}" a`. The arguments end at the line end or at one of the given keywords
+    fn parse_command(&mut self, until: &[TokenType]) -> Result<Expr> {
+        let callee = self.parse_expression()?;
+        let mut arguments = Vec::new();
+        while self
+            .peek_kind()
+            .is_some_and(|k| starts_operand(k) && !until.contains(k))
+        {
+            arguments.push(self.parse_expression()?);
+        }
+        let Some(last) = arguments.last() else {
+            return Ok(callee);
+        };
+        let span = callee.span.to(last.span);
+        return Ok(Expr {
+            kind: ExprKind::Call {
+                callee: Box::new(callee),
+                arguments,
+            },
+            span,
+        });
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expr> {
+        return self.parse_binary(0);
+    }
+
+    /// Parse binary operators of at least the given precedence by precedence climbing, operators of the same
+    /// precedence are left associative
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while let Some((operator, precedence)) = self.peek_kind().and_then(binary_operator) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.advance();
+            let right = self.parse_binary(precedence + 1)?;
+            left = Expr {
+                span: left.span.to(right.span),
+                kind: ExprKind::Binary {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            };
+        }
+        return Ok(left);
+    }
+
+    /// Parse `new`, `-` and `!` prefixes, which bind tighter than binary operators but looser than postfixes, so
+    /// `-a.b` is `-(a.b)`
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if let Some(new) = self.eat(&TokenType::New) {
+            let operand = self.parse_postfix()?;
+            return Ok(Expr {
+                span: new.span().to(operand.span),
+                kind: ExprKind::New(Box::new(operand)),
+            });
+        }
+        let operator = match self.peek_kind() {
+            Some(TokenType::Minus) => UnaryOperator::Negate,
+            Some(TokenType::Not) => UnaryOperator::Not,
+            _ => return self.parse_postfix(),
+        };
+        let start = self.advance().unwrap().span();
+        let operand = self.parse_unary()?;
+        return Ok(Expr {
+            span: start.to(operand.span),
+            kind: ExprKind::Unary {
+                operator,
+                operand: Box::new(operand),
+            },
+        });
+    }
+
+    /// Parse member accesses, calls, indexing and component accesses following an operand. The parentheses of
+    /// a call, the brackets of an index and the `<` of a component access must directly follow the operand, as
+    /// `f (x)` is a call statement with a parenthesised argument and `a < b` is a comparison
+    fn parse_postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            let adjacent = self.peek_span().start == self.previous.end;
+            match self.peek_kind() {
+                Some(TokenType::BracketOpen) if adjacent => {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect(&TokenType::BracketClose)?;
+                    expr = Expr {
+                        span: expr.span.to(self.previous),
+                        kind: ExprKind::Index {
+                            object: Box::new(expr),
+                            index: Box::new(index),
+                        },
+                    };
+                }
+                Some(TokenType::Less) if adjacent => {
+                    let Some(component) = self.try_component()? else {
+                        return Ok(expr);
+                    };
+                    expr = Expr {
+                        span: expr.span.to(self.previous),
+                        kind: ExprKind::Component {
+                            entity: Box::new(expr),
+                            component,
+                        },
+                    };
+                }
+                Some(TokenType::Dot) => {
+                    self.advance();
+                    let member = self.expect_identifier("member name")?;
+                    expr = Expr {
+                        span: expr.span.to(member.span),
+                        kind: ExprKind::Member {
+                            object: Box::new(expr),
+                            member,
+                        },
+                    };
+                }
+                Some(TokenType::ParanthesisOpen) if adjacent => {
+                    self.advance();
+                    let arguments =
+                        self.parse_list(&TokenType::ParanthesisClose, Parser::parse_expression)?;
+                    expr = Expr {
+                        span: expr.span.to(self.previous),
+                        kind: ExprKind::Call {
+                            callee: Box::new(expr),
+                            arguments,
+                        },
+                    };
+                }
+                Some(TokenType::BraceOpen) => {
+                    if !is_path(&expr) {
+                        return Err(ParseError {
+                            kind: ErrorKind::InvalidRecordType,
+                            span: expr.span,
+                        });
+                    }
+                    self.advance();
+                    let fields =
+                        self.parse_list(&TokenType::BraceClose, Parser::parse_field_value)?;
+                    expr = Expr {
+                        span: expr.span.to(self.previous),
+                        kind: ExprKind::Record {
+                            ty: Box::new(expr),
+                            fields,
+                        },
+                    };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// Parse the `name: value` of a field of a record literal
+    fn parse_field_value(&mut self) -> Result<(Identifier, Expr)> {
+        let name = self.expect_identifier("field name")?;
+        self.expect(&TokenType::Colon)?;
+        return Ok((name, self.parse_expression()?));
+    }
+
+    /// Parse the `<Component>` of a component access, or leave the `<` to be parsed as a comparison if it is not
+    /// followed by a type and a `>`
+    fn try_component(&mut self) -> Result<Option<TypeExpr>> {
+        let (position, previous) = (self.position, self.previous);
+        self.expect(&TokenType::Less)?;
+        if let Ok(component) = self.parse_type() {
+            if self.eat(&TokenType::Greater).is_some() {
+                return Ok(Some(component));
+            }
+        }
+        self.position = position;
+        self.previous = previous;
+        return Ok(None);
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected("expression"));
+        };
+        let literal = match token.kind() {
+            TokenType::LiteralInteger(value) => Some(Literal::Integer(*value)),
+            TokenType::LiteralFloat(value) => Some(Literal::Float(*value)),
+            TokenType::LiteralDuration(value) => Some(Literal::Duration(*value)),
+            TokenType::LiteralString(value) => Some(Literal::String(value.clone())),
+            TokenType::LiteralChar(value) => Some(Literal::Char(*value)),
+            TokenType::LiteralColor(value) => Some(Literal::Color(*value)),
+            TokenType::LiteralTemplate(segments) => Some(Literal::Template(segments.clone())),
+            TokenType::True => Some(Literal::Bool(true)),
+            TokenType::False => Some(Literal::Bool(false)),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            self.advance();
+            return Ok(Expr {
+                kind: ExprKind::Literal(literal),
+                span: token.span(),
+            });
+        }
+
+        let lambda = match token.kind() {
+            TokenType::ParanthesisOpen => self
+                .matching_close(0)
+                .is_some_and(|i| self.peek_kind_at(i + 1) == Some(&TokenType::Lambda)),
+            kind => kind.identifier().is_some() && self.peek_kind_at(1) == Some(&TokenType::Lambda),
+        };
+        if lambda {
+            let lambda = self.parse_lambda()?;
+            return Ok(Expr {
+                span: lambda.span,
+                kind: ExprKind::Lambda(lambda),
+            });
+        }
+
+        let kind = match token.kind() {
+            TokenType::SelfRef => {
+                self.advance();
+                ExprKind::SelfRef
+            }
+            TokenType::ParanthesisOpen => return self.parse_parenthesised(),
+            TokenType::BracketOpen => {
+                self.advance();
+                ExprKind::Array(
+                    self.parse_list(&TokenType::BracketClose, Parser::parse_expression)?,
+                )
+            }
+            TokenType::MatrixOpen | TokenType::VectorOpen => return self.parse_matrix(),
+            kind => match kind.identifier() {
+                Some(name) => {
+                    self.advance();
+                    ExprKind::Identifier(name.to_string())
+                }
+                None => return Err(self.unexpected("expression")),
+            },
+        };
+        return Ok(Expr {
+            kind,
+            span: token.span().to(self.previous),
+        });
+    }
+
+    /// Parse a tuple, or an expression within parentheses whose span then covers the parentheses
+    fn parse_parenthesised(&mut self) -> Result<Expr> {
+        let start = self.expect(&TokenType::ParanthesisOpen)?.span();
+        let mut elements = Vec::new();
+        let mut tuple = true;
+        if self.eat(&TokenType::ParanthesisClose).is_none() {
+            elements.push(self.parse_expression()?);
+            tuple = self.check(&TokenType::Comma);
+            while self.eat(&TokenType::Comma).is_some() && !self.check(&TokenType::ParanthesisClose)
+            {
+                elements.push(self.parse_expression()?);
+            }
+            self.expect(&TokenType::ParanthesisClose)?;
+        }
+        let span = start.to(self.previous);
+        return match tuple {
+            true => Ok(Expr {
+                kind: ExprKind::Tuple(elements),
+                span,
+            }),
+            false => Ok(Expr {
+                span,
+                ..elements.pop().unwrap()
+            }),
+        };
+    }
+
+    /// Parse the numeric elements of a matrix or column vector literal, rows are separated by `;`
+    fn parse_matrix(&mut self) -> Result<Expr> {
+        let open = self.advance().unwrap();
+        let mut rows = vec![Vec::new()];
+        while self.eat(&TokenType::ParanthesisClose).is_none() {
+            if self.eat(&TokenType::Semicolon).is_some() {
+                rows.push(Vec::new());
+                continue;
+            }
+            let element = self.parse_primary()?;
+            rows.last_mut().unwrap().push(element);
+        }
+        return Ok(Expr {
+            kind: ExprKind::Matrix {
+                rows,
+                column: open.kind() == &TokenType::VectorOpen,
+            },
+            span: open.span().to(self.previous),
+        });
+    }
+
+    /// Parse `(parameters...) => body` or `parameter => body`, where the body is either an expression on the
+    /// same line or an indented block, in which case the rest of the line is the return type
+    fn parse_lambda(&mut self) -> Result<Lambda> {
+        let start = self.peek_span();
+        let parameters = match self.eat(&TokenType::ParanthesisOpen) {
+            Some(_) => self.parse_list(&TokenType::ParanthesisClose, Parser::parse_parameter)?,
+            None => {
+                let name = self.expect_identifier("lambda parameters")?;
+                vec![Parameter {
+                    span: name.span,
+                    name,
+                    ty: None,
+                }]
+            }
+        };
+        self.expect(&TokenType::Lambda)?;
+        let return_type = match self.at_line_end() {
+            true => None,
+            false => self.try_return_type()?,
+        };
+        let body = match return_type.is_some() || self.at_line_end() {
+            true => LambdaBody::Block(self.parse_indented_block()?),
+            false => LambdaBody::Expression(Box::new(self.parse_expression()?)),
+        };
+        return Ok(Lambda {
+            parameters,
+            return_type,
+            body,
+            span: start.to(self.previous),
+        });
+    }
+
+    /// Parse the return type of a lambda, or leave the rest of the line to be parsed as its body if it is not a
+    /// type followed by an indented block
+    fn try_return_type(&mut self) -> Result<Option<TypeExpr>> {
+        let (position, previous) = (self.position, self.previous);
+        if let Ok(ty) = self.parse_type() {
+            if self.check(&TokenType::Newline) && self.peek_kind_at(1) == Some(&TokenType::Indent) {
+                return Ok(Some(ty));
+            }
+        }
+        self.position = position;
+        self.previous = previous;
+        return Ok(None);
+    }
+
+    /// Parse a `name` or `name: Type` parameter
+    fn parse_parameter(&mut self) -> Result<Parameter> {
+        let name = self.expect_identifier("parameter name")?;
+        let ty = match self.eat(&TokenType::Colon) {
+            Some(_) => Some(self.parse_type()?),
+            None => None,
+        };
+        return Ok(Parameter {
+            span: name.span.to(self.previous),
+            name,
+            ty,
+        });
+    }
+
+    pub fn parse_type(&mut self) -> Result<TypeExpr> {
+        let start = self.peek_span();
+        let kind = match self.peek_kind() {
+            Some(TokenType::BracketOpen) => {
+                self.advance();
+                let element = Box::new(self.parse_type()?);
+                let kind = match self.eat(&TokenType::Comma) {
+                    Some(_) => TypeKind::Map(element, Box::new(self.parse_type()?)),
+                    None => TypeKind::Array(element),
+                };
+                self.expect(&TokenType::BracketClose)?;
+                kind
+            }
+            Some(TokenType::BraceOpen) => {
+                self.advance();
+                let element = Box::new(self.parse_type()?);
+                self.expect(&TokenType::BraceClose)?;
+                TypeKind::Set(element)
+            }
+            Some(TokenType::ParanthesisOpen) => {
+                self.advance();
+                TypeKind::Tuple(self.parse_list(&TokenType::ParanthesisClose, Parser::parse_type)?)
+            }
+            _ => {
+                let path = self.parse_path("type")?;
+                let mut arguments = Vec::new();
+                if self.eat(&TokenType::Less).is_some() {
+                    arguments.push(self.parse_type()?);
+                    while self.eat(&TokenType::Greater).is_none() {
+                        self.expect(&TokenType::Comma)?;
+                        arguments.push(self.parse_type()?);
+                    }
+                }
+                TypeKind::Named { path, arguments }
+            }
+        };
+        return Ok(TypeExpr {
+            kind,
+            span: start.to(self.previous),
+        });
+    }
+
+    /// Parse names separated by dots, such as `HTTP.Header`
+    fn parse_path(&mut self, expected: &str) -> Result<Vec<Identifier>> {
+        let mut path = vec![self.expect_identifier(expected)?];
+        while self.eat(&TokenType::Dot).is_some() {
+            path.push(self.expect_identifier(expected)?);
+        }
+        return Ok(path);
+    }
+
+    /// Parse items separated by commas up to and including the closing token, a trailing comma is allowed
+    fn parse_list<T>(
+        &mut self,
+        close: &TokenType,
+        mut item: impl FnMut(&mut Parser) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while self.eat(close).is_none() {
+            items.push(item(self)?);
+            if !self.check(close) && self.eat(&TokenType::Comma).is_none() {
+                return Err(self.unexpected(&format!("`,` or {}", describe(close))));
+            }
+        }
+        return Ok(items);
+    }
+
+    /// Returns the offset from the current token of the parenthesis closing the one at `offset`, on the same line
+    fn matching_close(&self, offset: usize) -> Option<usize> {
+        let mut depth = 0;
+        for i in offset.. {
+            match self.peek_kind_at(i)? {
+                TokenType::ParanthesisOpen | TokenType::MatrixOpen | TokenType::VectorOpen => {
+                    depth += 1
+                }
+                TokenType::ParanthesisClose => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                TokenType::Newline => return None,
+                _ => (),
+            }
+        }
+        return None;
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.position);
+    }
+
+    fn peek_kind(&self) -> Option<&TokenType> {
+        return self.peek_kind_at(0);
+    }
+
+    fn peek_kind_at(&self, offset: usize) -> Option<&TokenType> {
+        return self.tokens.get(self.position + offset).map(|t| t.kind());
+    }
+
+    fn peek_span(&self) -> Span {
+        return self.peek().map_or(self.end, |t| t.span());
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position)?.clone();
+        self.position += 1;
+        if !matches!(
+            token.kind(),
+            TokenType::Newline | TokenType::Indent | TokenType::Dedent
+        ) {
+            self.previous = token.span();
+        }
+        return Some(token);
+    }
+
+    fn check(&self, kind: &TokenType) -> bool {
+        return self.peek_kind() == Some(kind);
+    }
+
+    /// Consume the current token if it is of the given kind
+    fn eat(&mut self, kind: &TokenType) -> Option<Token> {
+        return match self.check(kind) {
+            true => self.advance(),
+            false => None,
+        };
+    }
+
+    fn expect(&mut self, kind: &TokenType) -> Result<Token> {
+        return match self.eat(kind) {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected(&describe(kind))),
+        };
+    }
+
+    /// Consume an identifier, contextual keywords are identifiers outside of their clauses
+    fn expect_identifier(&mut self, expected: &str) -> Result<Identifier> {
+        let Some(name) = self.peek_kind().and_then(|k| k.identifier()) else {
+            return Err(self.unexpected(expected));
+        };
+        let name = name.to_string();
+        let token = self.advance().unwrap();
+        return Ok(Identifier {
+            name,
+            span: token.span(),
+        });
+    }
+
+    fn at_line_end(&self) -> bool {
+        return matches!(
+            self.peek_kind(),
+            None | Some(TokenType::Newline) | Some(TokenType::Dedent)
+        );
+    }
+
+    /// Consume the line break ending a line, the last line of a file may not have one. A line followed by an
+    /// indented block is ended by the dedent closing the block
+    fn expect_line_end(&mut self) -> Result<()> {
+        if self.eat(&TokenType::Newline).is_some()
+            || self.eat(&TokenType::Dedent).is_some()
+            || self.peek().is_none()
+        {
+            return Ok(());
+        }
+        return Err(self.unexpected("end of line"));
+    }
+
+    /// Error for the current token, or for the end of the file, in place of the expected construct
+    fn unexpected(&self, expected: &str) -> ParseError {
+        return match self.peek() {
+            Some(token) => ParseError {
+                kind: ErrorKind::Unexpected(expected.to_string(), token.kind().clone()),
+                span: token.span(),
+            },
+            None => ParseError {
+                kind: ErrorKind::UnexpectedEnd(expected.to_string()),
+                span: self.end,
+            },
+        };
+    }
+}
+
+/// Returns the binary operator of a token along with its precedence, operators of a higher precedence bind
+/// tighter
+fn binary_operator(kind: &TokenType) -> Option<(BinaryOperator, u8)> {
+    let operator = match kind {
+        TokenType::Or => (BinaryOperator::Or, 1),
+        TokenType::And => (BinaryOperator::And, 2),
+        TokenType::EqualEqual => (BinaryOperator::Equal, 3),
+        TokenType::NotEqual => (BinaryOperator::NotEqual, 3),
+        TokenType::Less => (BinaryOperator::Less, 4),
+        TokenType::LessEqual => (BinaryOperator::LessEqual, 4),
+        TokenType::Greater => (BinaryOperator::Greater, 4),
+        TokenType::GreaterEqual => (BinaryOperator::GreaterEqual, 4),
+        TokenType::Plus => (BinaryOperator::Add, 5),
+        TokenType::Minus => (BinaryOperator::Subtract, 5),
+        TokenType::Asterisk => (BinaryOperator::Multiply, 6),
+        TokenType::ForwardSlash => (BinaryOperator::Divide, 6),
+        _ => return None,
+    };
+    return Some(operator);
+}
+
+/// Returns true if the token may start an argument of a call statement
+fn starts_operand(kind: &TokenType) -> bool {
+    return kind.identifier().is_some()
+        || matches!(
+            kind,
+            TokenType::LiteralInteger(_)
+                | TokenType::LiteralFloat(_)
+                | TokenType::LiteralDuration(_)
+                | TokenType::LiteralString(_)
+                | TokenType::LiteralChar(_)
+                | TokenType::LiteralTemplate(_)
+                | TokenType::LiteralColor(_)
+                | TokenType::True
+                | TokenType::False
+                | TokenType::SelfRef
+                | TokenType::New
+                | TokenType::Not
+                | TokenType::ParanthesisOpen
+                | TokenType::BracketOpen
+                | TokenType::MatrixOpen
+                | TokenType::VectorOpen
+        );
+}
+
+/// Returns true if an expression is a name such as `Message` or `IO.Message`
+fn is_path(expr: &Expr) -> bool {
+    return match &expr.kind {
+        ExprKind::Identifier(_) => true,
+        ExprKind::Member { object, .. } => is_path(object),
+        _ => false,
+    };
+}
+
+/// Describe a token in an error message, such as `` `=>` `` or ``identifier `x` ``
+fn describe(kind: &TokenType) -> String {
+    let text = OPERATORS
+        .iter()
+        .chain(KEYWORDS.iter())
+        .chain(CONTEXTUAL_KEYWORDS.iter())
+        .find(|(_, k)| k == kind);
+    if let Some((text, _)) = text {
+        return format!("`{}`", text);
+    }
+    return match kind {
+        TokenType::Identifier(name) => format!("identifier `{}`", name),
+        TokenType::Newline => "end of line".to_string(),
+        TokenType::Indent => "indent".to_string(),
+        TokenType::Dedent => "dedent".to_string(),
+        TokenType::DocComment(_) => "doc comment".to_string(),
+        TokenType::MatrixOpen | TokenType::VectorOpen => "matrix".to_string(),
+        TokenType::Error(text) => format!("invalid `{}`", text),
+        kind if starts_operand(kind) => "literal".to_string(),
+        kind => format!("{:?}", kind),
+    };
+}
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// The expected construct, and the token found in its place
+    Unexpected(String, TokenType),
+    /// The expected construct, which is missing at the end of the file
+    UnexpectedEnd(String),
+    /// Doc comment at the end of a file, which documents nothing
+    DanglingDocComment,
+    /// Indented line which does not follow a line opening a block
+    UnexpectedIndent,
+    /// Line opening a block, such as `if condition`, which is not followed by an indented line
+    ExpectedBlock,
+    /// Operand of a record literal which is not the name of a record, such as `1 { a: 2 }`
+    InvalidRecordType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    kind: ErrorKind,
+    span: Span,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> &ErrorKind {
+        return &self.kind;
+    }
+
+    pub fn span(&self) -> Span {
+        return self.span;
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Unexpected(expected, found) => {
+                write!(f, "expected {}, found {}", expected, describe(found))
+            }
+            ErrorKind::UnexpectedEnd(expected) => {
+                write!(f, "expected {}, found end of file", expected)
+            }
+            ErrorKind::DanglingDocComment => {
+                write!(f, "doc comment is not followed by a declaration")
+            }
+            ErrorKind::UnexpectedIndent => write!(f, "unexpected indent"),
+            ErrorKind::ExpectedBlock => write!(f, "expected indented block"),
+            ErrorKind::InvalidRecordType => write!(f, "expected record name before `{{`"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorKind, ParseError, Parser};
+    use crate::ast::{
+        AccessMode, AccessTarget, AssignOperator, BinaryOperator, Declaration, DeclarationKind,
+        Expr, ExprKind, HandlerDecl, HandlerKind, LambdaBody, Literal, Module, OrderConstraint,
+        RecordKind, StatementKind, TypeKind, UnaryOperator,
+    };
+    use crate::lexer::Lexer;
+    use crate::token::{Float, Integer, TokenType};
+
+    fn parse(source: &str) -> Result<Module, ParseError> {
+        let tokens = Lexer::new().scan_str(source).unwrap();
+        return Parser::new(tokens).parse_module();
+    }
+
+    fn text<'a>(source: &'a str, expr: &Expr) -> &'a str {
+        return &source[expr.span.start..expr.span.end];
+    }
+
+    fn statement(declaration: &Declaration) -> &StatementKind {
+        return match &declaration.kind {
+            DeclarationKind::Statement(statement) => &statement.kind,
+            kind => panic!("expected a statement, found {:?}", kind),
+        };
+    }
+
+    #[test]
+    fn records() {
+        let source = "## Health of\n## an entity\ncomponent Health:\n    max: int\n\n    # note\n    items: [Item]\ncomponent StatusSuccess\n";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 2);
+
+        let health = &module.declarations[0];
+        assert_eq!(health.doc.as_deref(), Some("Health of\nan entity"));
+        assert_eq!(
+            &source[health.span.start..health.span.end],
+            "component Health:\n    max: int\n\n    # note\n    items: [Item]"
+        );
+        let DeclarationKind::Record(component) = &health.kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(component.kind, RecordKind::Component);
+        assert_eq!(component.name.name, "Health");
+        let names: Vec<&str> = component
+            .fields
+            .iter()
+            .map(|f| f.name.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["max", "items"]);
+        assert!(matches!(component.fields[1].ty.kind, TypeKind::Array(_)));
+
+        let DeclarationKind::Record(marker) = &module.declarations[1].kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(marker.name.name, "StatusSuccess");
+        assert!(marker.fields.is_empty() && module.declarations[1].doc.is_none());
+
+        let source = "state Counter
+    label   String
+    counter Integer
+structure Person
+    name: string
+    gender: string optional default=\"Unspecified\"
+    nickname ?: string
+    hobbies    [String]
+state.counter = 1
+";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 3);
+        let DeclarationKind::Record(counter) = &module.declarations[0].kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(counter.kind, RecordKind::State);
+        let label = &counter.fields[0];
+        assert_eq!(label.name.name, "label");
+        assert_eq!(&source[label.ty.span.start..label.ty.span.end], "String");
+        assert!(!label.optional && label.default.is_none());
+
+        let DeclarationKind::Record(person) = &module.declarations[1].kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(person.kind, RecordKind::Structure);
+        let fields: Vec<_> = person
+            .fields
+            .iter()
+            .map(|f| (f.name.name.as_str(), f.optional, f.default.is_some()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("name", false, false),
+                ("gender", true, true),
+                ("nickname", true, false),
+                ("hobbies", false, false),
+            ]
+        );
+        let gender = &person.fields[1];
+        assert_eq!(
+            gender.default.as_ref().unwrap().kind,
+            ExprKind::Literal(Literal::String("Unspecified".to_string()))
+        );
+        assert_eq!(
+            &source[gender.span.start..gender.span.end],
+            "gender: string optional default=\"Unspecified\""
+        );
+        assert!(matches!(
+            module.declarations[2].kind,
+            DeclarationKind::Statement(_)
+        ));
+
+        let source = "component A\n    ## doc\n    x: int\n    ## first\n    ## second\n    y: int\n    z: int\n";
+        let module = parse(source).unwrap();
+        let DeclarationKind::Record(record) = &module.declarations[0].kind else {
+            panic!("expected a record");
+        };
+        let docs: Vec<_> = record.fields.iter().map(|f| f.doc.as_deref()).collect();
+        assert_eq!(docs, vec![Some("doc"), Some("first\nsecond"), None]);
+        assert_eq!(
+            &source[record.fields[0].span.start..record.fields[0].span.end],
+            "x: int"
+        );
+    }
+
+    #[test]
+    fn variables() {
+        let source = "a: 20\nd (mutable): b\ndoe (Person): c\nconst e = 68\nexpose f: (1, 2)\n";
+        let module = parse(source).unwrap();
+        let variables: Vec<_> = module
+            .declarations
+            .iter()
+            .map(|d| match &d.kind {
+                DeclarationKind::Variable(variable) => variable,
+                kind => panic!("expected a variable, found {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            variables[0].value.kind,
+            ExprKind::Literal(Literal::Integer(Integer::I32(20)))
+        );
+        assert!(variables[1].mutable && variables[1].ty.is_none());
+        assert!(!variables[2].mutable && variables[2].ty.is_some());
+        assert!(variables[3].constant && variables[3].name.name == "e");
+        assert!(module.declarations[4].exposed);
+        assert_eq!(text(source, &variables[4].value), "(1, 2)");
+    }
+
+    #[test]
+    fn functions() {
+        let source = "function Square (x: number) => x\nfunction Token\n    (entity: Entity<HTTP.Header>) => entity.token\n";
+        let module = parse(source).unwrap();
+        let DeclarationKind::Function(square) = &module.declarations[0].kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(square.lambda.parameters[0].name.name, "x");
+        let LambdaBody::Expression(body) = &square.lambda.body else {
+            panic!("expected an expression body");
+        };
+        assert_eq!(body.kind, ExprKind::Identifier("x".to_string()));
+
+        let DeclarationKind::Function(token) = &module.declarations[1].kind else {
+            panic!("expected a function");
+        };
+        let ty = token.lambda.parameters[0].ty.as_ref().unwrap();
+        let TypeKind::Named { path, arguments } = &ty.kind else {
+            panic!("expected a named type");
+        };
+        assert_eq!(path[0].name, "Entity");
+        assert_eq!(
+            &source[arguments[0].span.start..arguments[0].span.end],
+            "HTTP.Header"
+        );
+        let LambdaBody::Expression(body) = &token.lambda.body else {
+            panic!("expected an expression body");
+        };
+        assert_eq!(text(source, body), "entity.token");
+    }
+
+    #[test]
+    fn blocks() {
+        let source = "function GetBearerToken
+    (entity: Entity<HTTP.Header>) => string
+        bearer = entity[HTTP.Header].Get(\"Authorization\")
+        if bearer.Empty()
+            return string.Empty()
+        else if bearer.Short()
+
+            # comment
+            while bearer.Short()
+                bearer += \"-\"
+        else
+            print bearer
+        return bearer.Explode().TryGet(1)
+square: (x) =>
+    return x * x
+print square(2)
+";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 3);
+        let DeclarationKind::Function(function) = &module.declarations[0].kind else {
+            panic!("expected a function");
+        };
+        let return_type = function.lambda.return_type.as_ref().unwrap();
+        assert_eq!(
+            &source[return_type.span.start..return_type.span.end],
+            "string"
+        );
+        let LambdaBody::Block(block) = &function.lambda.body else {
+            panic!("expected a block body");
+        };
+        assert_eq!(block.statements.len(), 3);
+        assert!(source[block.span.start..block.span.end].ends_with("TryGet(1)"));
+
+        let StatementKind::If {
+            then, otherwise, ..
+        } = &block.statements[1].kind
+        else {
+            panic!("expected an if statement");
+        };
+        assert!(matches!(
+            then.statements[0].kind,
+            StatementKind::Return(Some(_))
+        ));
+        let StatementKind::If {
+            then, otherwise, ..
+        } = &otherwise.as_ref().unwrap().statements[0].kind
+        else {
+            panic!("expected an else if statement");
+        };
+        assert!(matches!(
+            then.statements[0].kind,
+            StatementKind::While { .. }
+        ));
+        assert_eq!(otherwise.as_ref().unwrap().statements.len(), 1);
+
+        let DeclarationKind::Variable(square) = &module.declarations[1].kind else {
+            panic!("expected a variable");
+        };
+        let ExprKind::Lambda(lambda) = &square.value.kind else {
+            panic!("expected a lambda");
+        };
+        assert!(lambda.return_type.is_none() && matches!(lambda.body, LambdaBody::Block(_)));
+        assert_eq!(text(source, &square.value), "(x) =>\n    return x * x");
+
+        // a body which could be a type is only a return type when an indented block follows it
+        let source = "name: (x) => string\nhealth: (e) => e<Health>\nprint name(1)\n";
+        let module = parse(source).unwrap();
+        for declaration in &module.declarations[..2] {
+            let DeclarationKind::Variable(variable) = &declaration.kind else {
+                panic!("expected a variable");
+            };
+            let ExprKind::Lambda(lambda) = &variable.value.kind else {
+                panic!("expected a lambda");
+            };
+            assert!(lambda.return_type.is_none());
+            assert!(matches!(lambda.body, LambdaBody::Expression(_)));
+        }
+    }
+
+    #[test]
+    fn doc_comments() {
+        // doc comments inside blocks are skipped, one ending a block documents the declaration following it
+        let source = "if a
+    ## leading
+    b
+    ## trailing
+c
+if a
+    b
+## between
+else
+    ## leading
+    c
+function f
+    ## leading
+    (x) => x
+";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 4);
+        let StatementKind::If { then, .. } = statement(&module.declarations[0]) else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(then.statements.len(), 1);
+        assert_eq!(module.declarations[1].doc.as_deref(), Some("trailing"));
+        let StatementKind::If { otherwise, .. } = statement(&module.declarations[2]) else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(otherwise.as_ref().unwrap().statements.len(), 1);
+        assert!(module.declarations[3].doc.is_none());
+    }
+
+    #[test]
+    fn statements() {
+        let source =
+            "use IO\nprint \"a = {}\" a (b)\nMath.Power(2, 5)\nstate.counter += 1\nreturn\n";
+        let module = parse(source).unwrap();
+        let StatementKind::Use(path) = statement(&module.declarations[0]) else {
+            panic!("expected use");
+        };
+        assert_eq!(path[0].name, "IO");
+
+        let StatementKind::Expression(print) = statement(&module.declarations[1]) else {
+            panic!("expected an expression");
+        };
+        let ExprKind::Call { callee, arguments } = &print.kind else {
+            panic!("expected a call statement");
+        };
+        assert_eq!(text(source, callee), "print");
+        let arguments: Vec<&str> = arguments.iter().map(|a| text(source, a)).collect();
+        assert_eq!(arguments, vec!["\"a = {}\"", "a", "(b)"]);
+
+        let StatementKind::Expression(power) = statement(&module.declarations[2]) else {
+            panic!("expected an expression");
+        };
+        let ExprKind::Call { arguments, .. } = &power.kind else {
+            panic!("expected a call");
+        };
+        assert_eq!(arguments.len(), 2);
+
+        let StatementKind::Assignment {
+            target, operator, ..
+        } = statement(&module.declarations[3])
+        else {
+            panic!("expected an assignment");
+        };
+        assert_eq!(text(source, target), "state.counter");
+        assert_eq!(*operator, AssignOperator::Add);
+        assert_eq!(
+            statement(&module.declarations[4]),
+            &StatementKind::Return(None)
+        );
+    }
+
+    /// Evaluate an arithmetic expression in floating point
+    fn evaluate(expr: &Expr) -> f64 {
+        return match &expr.kind {
+            ExprKind::Literal(Literal::Integer(Integer::I32(v))) => *v as f64,
+            ExprKind::Literal(Literal::Float(Float::F32(v))) => *v as f64,
+            ExprKind::Unary {
+                operator: UnaryOperator::Negate,
+                operand,
+            } => -evaluate(operand),
+            ExprKind::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let (left, right) = (evaluate(left), evaluate(right));
+                match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left * right,
+                    BinaryOperator::Divide => left / right,
+                    operator => panic!("unexpected operator {:?}", operator),
+                }
+            }
+            kind => panic!("unexpected expression {:?}", kind),
+        };
+    }
+
+    /// Render an expression with parentheses around each operation
+    fn render(source: &str, expr: &Expr) -> String {
+        return match &expr.kind {
+            ExprKind::Binary {
+                operator,
+                left,
+                right,
+            } => format!(
+                "({} {:?} {})",
+                render(source, left),
+                operator,
+                render(source, right)
+            ),
+            ExprKind::Unary { operator, operand } => {
+                format!("({:?} {})", operator, render(source, operand))
+            }
+            ExprKind::Index { object, index } => {
+                format!("{}[{}]", render(source, object), render(source, index))
+            }
+            ExprKind::Component { entity, component } => format!(
+                "{}<{}>",
+                render(source, entity),
+                &source[component.span.start..component.span.end]
+            ),
+            ExprKind::Call { callee, arguments } => {
+                let arguments: Vec<String> = arguments.iter().map(|a| render(source, a)).collect();
+                format!("{}({})", render(source, callee), arguments.join(", "))
+            }
+            ExprKind::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| render(source, e)).collect();
+                format!("({})", elements.join(", "))
+            }
+            _ => text(source, expr).to_string(),
+        };
+    }
+
+    #[test]
+    fn arithmetic() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/examples/hello-math/main.ys"
+        );
+        let source = std::fs::read_to_string(path).unwrap();
+        let module = parse(&source).unwrap();
+        assert_eq!(module.declarations.len(), 15);
+
+        let values: Vec<f64> = module.declarations[3..7]
+            .iter()
+            .map(|declaration| {
+                let StatementKind::Expression(print) = statement(declaration) else {
+                    panic!("expected an expression");
+                };
+                let ExprKind::Call { arguments, .. } = &print.kind else {
+                    panic!("expected a call statement");
+                };
+                assert_eq!(arguments.len(), 1);
+                return evaluate(&arguments[0]);
+            })
+            .collect();
+        assert_eq!(values, vec![39.75, -15616.0, -1300.0, 39.75]);
+
+        let rendered: Vec<String> = module.declarations[2..]
+            .iter()
+            .map(|declaration| {
+                let StatementKind::Expression(print) = statement(declaration) else {
+                    panic!("expected an expression");
+                };
+                return render(&source, print);
+            })
+            .collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "print(\"Hello, Math!\")",
+                "print(((13 Add 56) Subtract ((24 Multiply 78) Divide 64)))",
+                "print(((((((67 Add 3) Multiply 5) Subtract 6) Add 9) Subtract (-23)) Add (10 Subtract ((2456 Subtract 678) Multiply 9))))",
+                "print((((((((336 Divide 2) Divide 2) Multiply 5) Subtract ((3 Multiply 7) Divide 3)) Subtract ((3 Divide 2) Multiply 1000)) Subtract 245) Add (34 Subtract 2)))",
+                "print(((13.0 Add 56.0) Subtract ((24.0 Multiply 78.0) Divide 64.0)))",
+                "print(Math.Power(2, 5))",
+                "print(Math.Power(5, 2))",
+                "print(Math.Power(100, -2))",
+                "print(Math.Power(3467, -34))",
+                "print(Math.Sqrt(400, 2))",
+                "print(Math.Sqrt(300, 5))",
+                "print(((1, 2) Add (5, 3)))",
+                "print(((1.0, 2) Add (5.0, 3)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let expression = |source: &str| {
+            let tokens = Lexer::new().scan_str(source).unwrap();
+            let expr = Parser::new(tokens).parse_expression().unwrap();
+            assert_eq!(text(source, &expr), source);
+            return render(source, &expr);
+        };
+        assert_eq!(
+            expression("a + b * c - d / e"),
+            "((a Add (b Multiply c)) Subtract (d Divide e))"
+        );
+        assert_eq!(
+            expression("a < b + 1 && !c || d == -e"),
+            "(((a Less (b Add 1)) And (Not c)) Or (d Equal (Negate e)))"
+        );
+        assert_eq!(
+            expression("-entity[Health].hp * 2"),
+            "((Negate entity[Health].hp) Multiply 2)"
+        );
+        assert_eq!(
+            expression("event.receiver<Armor>.armor - a<b"),
+            "((event.receiver<Armor>.armor Subtract a) Less b)"
+        );
+        assert_eq!(
+            expression("items[i - 1] >= x<y>"),
+            "(items[(i Subtract 1)] GreaterEqual x<y>)"
+        );
+        // every level of binary operators is left associative
+        assert_eq!(
+            expression("a - b + c - d"),
+            "(((a Subtract b) Add c) Subtract d)"
+        );
+        assert_eq!(
+            expression("a / b * c / d"),
+            "(((a Divide b) Multiply c) Divide d)"
+        );
+        assert_eq!(
+            expression("a <= b > c >= d < e"),
+            "((((a LessEqual b) Greater c) GreaterEqual d) Less e)"
+        );
+        assert_eq!(
+            expression("a != b == c != d"),
+            "(((a NotEqual b) Equal c) NotEqual d)"
+        );
+        assert_eq!(
+            expression("a && b && c || d || e"),
+            "((((a And b) And c) Or d) Or e)"
+        );
+        // a level binds tighter than the levels before it in `binary_operator`
+        assert_eq!(
+            expression("a || b && c == d < e + f * g"),
+            "(a Or (b And (c Equal (d Less (e Add (f Multiply g))))))"
+        );
+        assert_eq!(
+            expression("-a * !b - --c"),
+            "(((Negate a) Multiply (Not b)) Subtract (Negate (Negate c)))"
+        );
+        assert_eq!(expression("a[0]-1"), "(a[0] Subtract 1)");
+        assert_eq!(expression("f(x)[1]-2"), "(f(x)[1] Subtract 2)");
+
+        // the `-` is not the sign of an argument of a call statement
+        let module = parse("print a[0]-1\nx: a[0]-1\n").unwrap();
+        let StatementKind::Expression(print) = statement(&module.declarations[0]) else {
+            panic!("expected an expression");
+        };
+        let ExprKind::Call { arguments, .. } = &print.kind else {
+            panic!("expected a call statement");
+        };
+        assert_eq!(arguments.len(), 1);
+        assert!(matches!(
+            module.declarations[1].kind,
+            DeclarationKind::Variable(_)
+        ));
+    }
+
+    #[test]
+    fn systems() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/examples/http-crud/auth/token.ecsl"
+        );
+        let source = std::fs::read_to_string(path).unwrap();
+        let module = parse(&source).unwrap();
+        assert_eq!(module.declarations.len(), 5);
+        let DeclarationKind::System(resolver) = &module.declarations[2].kind else {
+            panic!("expected a system");
+        };
+        assert_eq!(resolver.name.name, "TokenResolver");
+        let accesses: Vec<_> = resolver
+            .accesses
+            .iter()
+            .map(|a| (a.mode, a.target, a.path[0].name.as_str()))
+            .collect();
+        assert_eq!(
+            accesses,
+            vec![
+                (AccessMode::Read, AccessTarget::Component, "Token"),
+                (AccessMode::Without, AccessTarget::Component, "StatusFail"),
+                (
+                    AccessMode::Without,
+                    AccessTarget::Component,
+                    "StatusSuccess"
+                ),
+            ]
+        );
+        let dependency = &resolver.dependencies[0];
+        assert_eq!(dependency.name.name, "db");
+        assert_eq!(
+            &source[dependency.ty.span.start..dependency.ty.span.end],
+            "Postgres.Connection"
+        );
+        assert_eq!(
+            &source[dependency.span.start..dependency.span.end],
+            "depends db [Postgres.Connection]"
+        );
+        let LambdaBody::Block(body) = &resolver.lambda.body else {
+            panic!("expected a block body");
+        };
+        assert_eq!(body.statements.len(), 5);
+
+        let source = "system Printer
+    after           Incrementer
+    on event        Increment
+    foreach state   Counter
+    filter event.value > 50
+    (state, event)  =>
+        state.counter += 1
+print 1
+";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 2);
+        let DeclarationKind::System(printer) = &module.declarations[0].kind else {
+            panic!("expected a system");
+        };
+        assert_eq!(printer.ordering[0].constraint, OrderConstraint::After);
+        assert_eq!(printer.ordering[0].system[0].name, "Incrementer");
+        let accesses: Vec<_> = printer
+            .accesses
+            .iter()
+            .map(|a| (a.mode, a.target, &source[a.span.start..a.span.end]))
+            .collect();
+        assert_eq!(
+            accesses,
+            vec![
+                (
+                    AccessMode::On,
+                    AccessTarget::Event,
+                    "on event        Increment"
+                ),
+                (
+                    AccessMode::Foreach,
+                    AccessTarget::State,
+                    "foreach state   Counter"
+                ),
+            ]
+        );
+        assert_eq!(text(source, &printer.filters[0]), "event.value > 50");
+        assert_eq!(printer.lambda.parameters.len(), 2);
+
+        let error = parse("system S\n    handles event E\n    e => e\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected system clause or lambda, found identifier `handles`"
+        );
+    }
+
+    #[test]
+    fn handlers() {
+        let source = "handle event Start
+    () => Empty
+        print(1)
+hook Foo (event) => event.value
+handle Temperature
+    filter event.value > 50
+    (event) => print(event.value)
+";
+        let module = parse(source).unwrap();
+        assert_eq!(module.declarations.len(), 3);
+        let handlers: Vec<&HandlerDecl> = module
+            .declarations
+            .iter()
+            .map(|declaration| match &declaration.kind {
+                DeclarationKind::Handler(handler) => handler,
+                _ => panic!("expected a handler"),
+            })
+            .collect();
+        assert_eq!(handlers[0].kind, HandlerKind::Handle);
+        assert_eq!(handlers[0].event[0].name, "Start");
+        let return_type = handlers[0].lambda.return_type.as_ref().unwrap();
+        assert_eq!(
+            &source[return_type.span.start..return_type.span.end],
+            "Empty"
+        );
+        let LambdaBody::Block(body) = &handlers[0].lambda.body else {
+            panic!("expected a block body");
+        };
+        assert_eq!(body.statements.len(), 1);
+
+        assert_eq!(handlers[1].kind, HandlerKind::Hook);
+        assert_eq!(handlers[1].event[0].name, "Foo");
+        assert!(matches!(handlers[1].lambda.body, LambdaBody::Expression(_)));
+        assert_eq!(text(source, &handlers[2].filters[0]), "event.value > 50");
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../docs/examples/modules/main.ys"
+        );
+        let source = std::fs::read_to_string(path).unwrap();
+        let start = source.find("handle event Start").unwrap();
+        let module = parse(&source[start..]).unwrap();
+        assert!(matches!(
+            module.declarations[0].kind,
+            DeclarationKind::Handler(_)
+        ));
+
+        let error = parse("handle event Start\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ExpectedBlock);
+    }
+
+    #[test]
+    fn events() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../docs/examples");
+        let source = std::fs::read_to_string(format!("{}/hello-state/main.ys", examples)).unwrap();
+        let module = parse(&source).unwrap();
+        assert_eq!(module.declarations.len(), 13);
+        let DeclarationKind::Record(increment) = &module.declarations[2].kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(increment.kind, RecordKind::Event);
+        assert!(increment.fields.is_empty());
+
+        let DeclarationKind::System(printer) = &module.declarations[4].kind else {
+            panic!("expected a system");
+        };
+        let LambdaBody::Block(body) = &printer.lambda.body else {
+            panic!("expected a block body");
+        };
+        let StatementKind::Emit { event, target, .. } = &body.statements[0].kind else {
+            panic!("expected an emit statement");
+        };
+        let ExprKind::Call { callee, arguments } = &event.kind else {
+            panic!("expected a call statement");
+        };
+        assert_eq!(text(&source, callee), "IO.Print");
+        assert_eq!(arguments.len(), 3);
+        assert!(target.is_none());
+
+        let StatementKind::Assignment { value, .. } = statement(&module.declarations[5]) else {
+            panic!("expected an assignment");
+        };
+        let ExprKind::New(record) = &value.kind else {
+            panic!("expected new");
+        };
+        let ExprKind::Record { ty, fields } = &record.kind else {
+            panic!("expected a record literal");
+        };
+        assert_eq!(text(&source, ty), "Counter");
+        assert_eq!(fields[0].0.name, "label");
+        assert_eq!(text(&source, &fields[0].1), "\"One\"");
+
+        let StatementKind::Emit { event, target, .. } = statement(&module.declarations[10]) else {
+            panic!("expected an emit statement");
+        };
+        assert_eq!(text(&source, event), "Increment");
+        assert_eq!(text(&source, target.as_ref().unwrap()), "one");
+
+        let source = "emit Foo with priority 3\nemit Foo 1 to one with priority -1\n";
+        let module = parse(source).unwrap();
+        let StatementKind::Emit {
+            event, priority, ..
+        } = statement(&module.declarations[0])
+        else {
+            panic!("expected an emit statement");
+        };
+        assert_eq!(text(source, event), "Foo");
+        assert_eq!(text(source, priority.as_ref().unwrap()), "3");
+        let StatementKind::Emit {
+            event,
+            target,
+            priority,
+        } = statement(&module.declarations[1])
+        else {
+            panic!("expected an emit statement");
+        };
+        assert_eq!(text(source, event), "Foo 1");
+        assert_eq!(text(source, target.as_ref().unwrap()), "one");
+        assert_eq!(text(source, priority.as_ref().unwrap()), "-1");
+        let error = parse("emit Foo with 3\n").unwrap_err();
+        assert_eq!(error.to_string(), "expected `priority`, found literal");
+
+        let source =
+            std::fs::read_to_string(format!("{}/hello-events/script.ys", examples)).unwrap();
+        let module = parse(&source).unwrap();
+        assert_eq!(module.declarations.len(), 9);
+        let DeclarationKind::Record(payload) = &module.declarations[2].kind else {
+            panic!("expected a record");
+        };
+        assert_eq!(payload.kind, RecordKind::Event);
+        let fields: Vec<&str> = payload
+            .fields
+            .iter()
+            .map(|f| f.name.name.as_str())
+            .collect();
+        assert_eq!(fields, vec!["hello", "sender"]);
+        let StatementKind::Emit { event, .. } = statement(&module.declarations[5]) else {
+            panic!("expected an emit statement");
+        };
+        assert_eq!(
+            text(&source, event),
+            "Message { message: 'hello!', sender: 'yonder' }"
+        );
+
+        let source =
+            std::fs::read_to_string(format!("{}/hello-world/script.ys", examples)).unwrap();
+        let module = parse(&source).unwrap();
+        assert_eq!(module.declarations.len(), 2);
+        assert!(matches!(
+            statement(&module.declarations[1]),
+            StatementKind::Emit { target: None, .. }
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse("component\n").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Unexpected("component name".to_string(), TokenType::Newline)
+        );
+        assert_eq!((error.span().start, error.span().end), (9, 10));
+        assert_eq!(
+            error.to_string(),
+            "expected component name, found end of line"
+        );
+
+        let error = parse("f(a b)\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected `,` or `)`, found identifier `b`"
+        );
+        assert_eq!(error.span().start, 4);
+
+        let error = parse("a = 1\n    b = 2\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedIndent);
+        assert_eq!((error.span().start, error.span().end), (6, 10));
+        assert_eq!(error.to_string(), "unexpected indent");
+
+        let error = parse("if a\nb\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ExpectedBlock);
+        assert_eq!((error.span().start, error.span().end), (5, 6));
+        let error = parse("f: (x) =>\n").unwrap_err();
+        assert_eq!(error.to_string(), "expected indented block");
+
+        let error = parse("x: 1 {a: 2}\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidRecordType);
+        assert_eq!((error.span().start, error.span().end), (3, 4));
+        assert_eq!(error.to_string(), "expected record name before `{`");
+        let error = parse("emit f(x) {a: 2}\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidRecordType);
+        assert!(parse("emit IO.Message {a: 2}\n").is_ok());
+
+        let error = parse("x: 1\n## nothing\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DanglingDocComment);
+        let error = Parser::new(Vec::new()).parse_expression().unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::UnexpectedEnd("expression".to_string())
+        );
+    }
+}
@@ -1,3 +1,208 @@
-struct Context {
-    
-}
\ No newline at end of file
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::OnceLock;
+
+use crate::dictionary::{self, Record};
+use crate::fsm::{Dfa, Nfa, SymbolSet, DEAD, XID_CONTINUE, XID_START};
+use crate::token::{Dictionary, TokenType};
+
+/// Kind of lexeme recognised by the scanner
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lexeme {
+    /// Keyword or operator
+    Token(TokenType),
+    /// Identifier, named by the text of the lexeme
+    Identifier,
+    /// Unsigned numeric literal, including its radix prefix, separators, decimals and suffix or duration units
+    Number,
+}
+
+/// Value accepted by the automaton of a scanner
+#[derive(Debug, Clone, Copy)]
+enum Accept {
+    /// Record of the dictionary, by index
+    Record(usize),
+    Number,
+}
+
+/// Scanner recognising the records of a dictionary along with numeric literals with a single DFA. The longest
+/// lexeme wins, and a lexeme matching several records resolves to the record of the highest priority
+#[derive(Clone)]
+pub struct Scanner {
+    records: dictionary::Dictionary<Lexeme>,
+    dfa: Dfa<Accept>,
+}
+
+impl Scanner {
+    /// Returns the scanner of the default dictionary, which is only compiled once per process
+    pub fn new() -> Scanner {
+        static SCANNER: OnceLock<Scanner> = OnceLock::new();
+        return SCANNER
+            .get_or_init(|| Scanner::from_dictionary(&Dictionary::new()))
+            .clone();
+    }
+
+    /// Compile the records of a dictionary into a scanner
+    pub fn from_dictionary(dictionary: &Dictionary) -> Scanner {
+        let records = dictionary.records().clone();
+        let mut nfa = Nfa::new();
+        for (i, (record, _)) in records.records().enumerate() {
+            match record {
+                Record::Exact(priority, text) => {
+                    nfa.add_literal(text, *priority, Accept::Record(i))
+                }
+                // patterns are validated when they are added to the dictionary
+                Record::Pattern(priority, pattern) => {
+                    nfa.add_pattern(pattern, *priority, Accept::Record(i))
+                        .unwrap();
+                }
+            }
+        }
+
+        // records win over numbers of the same length, a dot is only part of a number when a digit follows,
+        // e.g. `1.5` but not `1.foo`
+        let digit = SymbolSet::from_fn(|s| s < 128 && (s as u8).is_ascii_digit());
+        let continues = SymbolSet::from_fn(|s| {
+            s < 128 && (s as u8 == b'_' || (s as u8).is_ascii_alphanumeric())
+                || s == XID_START
+                || s == XID_CONTINUE
+        });
+        let number = nfa.add_state();
+        let dot = nfa.add_state();
+        nfa.add_transition(Nfa::<Accept>::START, digit, number);
+        nfa.add_transition(number, continues, number);
+        nfa.add_transition(number, SymbolSet::of("."), dot);
+        nfa.add_transition(dot, digit, number);
+        nfa.set_accept(number, i32::MIN, Accept::Number);
+
+        return Scanner {
+            records,
+            dfa: Dfa::new(&nfa),
+        };
+    }
+
+    /// Consume the longest lexeme starting with the already consumed `c`, returning its kind along with its text.
+    /// Nothing more is consumed if no lexeme starts with `c`
+    pub fn consume(&self, c: char, chars: &mut Peekable<Chars>) -> Option<(Lexeme, String)> {
+        let mut text = String::new();
+        let mut accepted = Vec::new();
+        let mut state = Dfa::<Accept>::START;
+        for (i, c) in [c].into_iter().chain(chars.clone()).enumerate() {
+            state = self.dfa.next(state, c);
+            if state == DEAD {
+                break;
+            }
+            text.push(c);
+            if !self.dfa.accepts(state).is_empty() {
+                accepted.push((i + 1, text.len(), state));
+            }
+        }
+
+        // patterns which are not exact in the automaton are checked against their regex, falling back to the
+        // next record or to a shorter lexeme
+        for (len, bytes, state) in accepted.into_iter().rev() {
+            let word = &text[..bytes];
+            let lexeme = self
+                .dfa
+                .accepts(state)
+                .iter()
+                .find_map(|accept| match accept {
+                    Accept::Number => Some(Lexeme::Number),
+                    Accept::Record(i)
+                        if self.records.is_exact(*i) || self.records.is_match(*i, word) =>
+                    {
+                        Some(self.records.record(*i).1.clone())
+                    }
+                    Accept::Record(_) => None,
+                });
+            if let Some(lexeme) = lexeme {
+                for _ in 1..len {
+                    chars.next();
+                }
+                return Some((lexeme, word.to_string()));
+            }
+        }
+        return None;
+    }
+
+    /// Returns the lexeme of a whole text, or none if the text is not a single lexeme
+    pub fn get(&self, text: &str) -> Option<Lexeme> {
+        let mut chars = text.chars().peekable();
+        let (lexeme, word) = self.consume(chars.next()?, &mut chars)?;
+        return match word.len() == text.len() {
+            true => Some(lexeme),
+            false => None,
+        };
+    }
+
+    pub fn state_count(&self) -> usize {
+        return self.dfa.state_count();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Lexeme, Scanner};
+    use crate::token::TokenType;
+
+    fn lexemes(source: &str) -> Vec<(Lexeme, String)> {
+        let scanner = Scanner::new();
+        let mut chars = source.chars().peekable();
+        let mut lexemes = Vec::new();
+        while let Some(c) = chars.next() {
+            if let Some((lexeme, text)) = scanner.consume(c, &mut chars) {
+                lexemes.push((lexeme, text));
+            }
+        }
+        return lexemes;
+    }
+
+    #[test]
+    fn lexemes_of_line() {
+        let token = |kind, text: &str| (Lexeme::Token(kind), text.to_string());
+        let identifier = |text: &str| (Lexeme::Identifier, text.to_string());
+        let number = |text: &str| (Lexeme::Number, text.to_string());
+        assert_eq!(
+            lexemes("for_each => format(1.5s, x.foo1, 0xFF_u) >= été"),
+            vec![
+                identifier("for_each"),
+                token(TokenType::Lambda, "=>"),
+                identifier("format"),
+                token(TokenType::ParanthesisOpen, "("),
+                number("1.5s"),
+                token(TokenType::Comma, ","),
+                identifier("x"),
+                token(TokenType::Dot, "."),
+                identifier("foo1"),
+                token(TokenType::Comma, ","),
+                number("0xFF_u"),
+                token(TokenType::ParanthesisClose, ")"),
+                token(TokenType::GreaterEqual, ">="),
+                identifier("été"),
+            ]
+        );
+        assert_eq!(
+            lexemes("for 1.foo on"),
+            vec![
+                token(TokenType::For, "for"),
+                number("1"),
+                token(TokenType::Dot, "."),
+                identifier("foo"),
+                token(TokenType::On, "on"),
+            ]
+        );
+    }
+
+    #[test]
+    fn whole_lexemes() {
+        let scanner = Scanner::new();
+        assert_eq!(scanner.get("while"), Some(Lexeme::Token(TokenType::While)));
+        assert_eq!(scanner.get("whiles"), Some(Lexeme::Identifier));
+        assert_eq!(
+            scanner.get("?:"),
+            Some(Lexeme::Token(TokenType::OptionalColon))
+        );
+        assert_eq!(scanner.get("a b"), None);
+        assert_eq!(scanner.get("·b"), None);
+    }
+}
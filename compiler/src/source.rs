@@ -0,0 +1,163 @@
+use std::ops::Range;
+
+use crate::token::{Position, Span};
+
+/// Replacement of a range of bytes of a source text, such as the change of an editor buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: &str) -> Edit {
+        return Edit {
+            range,
+            text: text.to_string(),
+        };
+    }
+
+    /// Returns the text resulting from the edit, or none if the range is not within the text or not on its
+    /// character boundaries
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if self.range.start > self.range.end {
+            return None;
+        }
+        let before = text.get(..self.range.start)?;
+        let after = text.get(self.range.end..)?;
+        return Some([before, &self.text, after].concat());
+    }
+}
+
+/// Line index of a source file, converting the byte offsets of spans into lines and columns. Offsets must be
+/// on character boundaries of the text
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    text: String,
+    /// Byte offset of the start of each line
+    lines: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(text: String) -> SourceMap {
+        let mut lines = vec![0];
+        lines.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        return SourceMap { text, lines };
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.text;
+    }
+
+    /// Returns the source text covered by a span
+    pub fn slice(&self, span: Span) -> &str {
+        return &self.text[span.start..span.end];
+    }
+
+    pub fn line_count(&self) -> usize {
+        return self.lines.len();
+    }
+
+    /// Returns the zero-based line containing a byte offset
+    pub fn line(&self, offset: usize) -> u32 {
+        return match self.lines.binary_search(&offset) {
+            Ok(line) => line as u32,
+            Err(next) => next as u32 - 1,
+        };
+    }
+
+    /// Returns the byte offset of the start of a line
+    pub fn line_start(&self, line: u32) -> Option<usize> {
+        return self.lines.get(line as usize).copied();
+    }
+
+    /// Returns the line and column of a byte offset, where the column counts characters as in `Token::pos`
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.line(offset);
+        let col = self.line_prefix(line, offset).chars().count();
+        return Position {
+            line,
+            col: col as u32,
+        };
+    }
+
+    /// Returns the column of a byte offset in bytes from the start of its line
+    pub fn utf8_col(&self, offset: usize) -> u32 {
+        return self.line_prefix(self.line(offset), offset).len() as u32;
+    }
+
+    /// Returns the column of a byte offset in UTF-16 code units from the start of its line, as used by editors
+    pub fn utf16_col(&self, offset: usize) -> u32 {
+        let prefix = self.line_prefix(self.line(offset), offset);
+        return prefix.chars().map(char::len_utf16).sum::<usize>() as u32;
+    }
+
+    /// Returns the byte offset of a line and column, where the column counts characters
+    pub fn offset(&self, pos: Position) -> Option<usize> {
+        let start = self.line_start(pos.line)?;
+        let end = self.line_start(pos.line + 1).unwrap_or(self.text.len());
+        let line = &self.text[start..end];
+        return match line.char_indices().nth(pos.col as usize) {
+            Some((i, _)) => Some(start + i),
+            None if line.chars().count() == pos.col as usize => Some(end),
+            None => None,
+        };
+    }
+
+    fn line_prefix(&self, line: u32, offset: usize) -> &str {
+        return &self.text[self.lines[line as usize]..offset];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Range;
+
+    use super::{Edit, SourceMap};
+    use crate::token::{FileId, Position, Span};
+
+    #[test]
+    fn positions() {
+        let map = SourceMap::new("a = 1\nb = 'é😀'\n".to_string());
+        assert_eq!(map.line_count(), 3);
+        assert_eq!(map.line(0), 0);
+        assert_eq!(map.line(5), 0);
+        assert_eq!(map.line(6), 1);
+        assert_eq!(map.line_start(1), Some(6));
+
+        // `'` after the two non-ascii characters
+        let offset = 6 + 5 + 2 + 4;
+        assert_eq!(map.position(offset), Position { line: 1, col: 7 });
+        assert_eq!(map.utf8_col(offset), 11);
+        assert_eq!(map.utf16_col(offset), 8);
+        assert_eq!(map.offset(Position { line: 1, col: 7 }), Some(offset));
+        assert_eq!(
+            map.offset(Position { line: 1, col: 9 }),
+            Some(map.text().len())
+        );
+        assert_eq!(map.offset(Position { line: 1, col: 10 }), None);
+
+        let span = Span {
+            file: FileId(0),
+            start: 10,
+            end: offset + 1,
+        };
+        assert_eq!(map.slice(span), "'é😀'");
+    }
+
+    #[test]
+    fn edits() {
+        let text = "a = 'é'\n";
+        assert_eq!(
+            Edit::new(4..7, "2").apply(text).as_deref(),
+            Some("a = 2'\n")
+        );
+        assert_eq!(
+            Edit::new(8..8, "b").apply(text).as_deref(),
+            Some("a = 'é'b\n")
+        );
+        assert_eq!(Edit::new(0..10, "").apply(text), None);
+        assert_eq!(Edit::new(6..6, "").apply(text), None);
+        assert_eq!(Edit::new(Range { start: 2, end: 1 }, "").apply(text), None);
+    }
+}
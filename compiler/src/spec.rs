@@ -0,0 +1,166 @@
+//! Format of token.yaml, shared by build.rs, which generates the tokens of the language from it, and by the
+//! dictionary, which loads extensions written in the same format.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+pub struct Spec {
+    #[serde(default)]
+    pub operators: Section,
+    #[serde(default)]
+    pub keywords: Section,
+    #[serde(default)]
+    pub contextual_keywords: Section,
+    /// Regular expressions, only used by extensions
+    #[serde(default)]
+    pub patterns: Section,
+    /// Openers of line comments, only used by extensions
+    #[serde(default)]
+    pub line_comments: Section,
+    /// Openers of doc comments, only used by extensions
+    #[serde(default)]
+    pub doc_comments: Section,
+    /// Opener and closer of block comments separated by whitespace, such as `"{- -}"`, only used by extensions
+    #[serde(default)]
+    pub block_comments: Section,
+    /// Quote characters of string literals, only used by extensions
+    #[serde(default)]
+    pub strings: Section,
+}
+
+/// Entries of a section in declaration order, duplicated keys are preserved so they can be reported
+#[derive(Default)]
+pub struct Section(pub Vec<(String, String)>);
+
+impl Spec {
+    /// Entries of the sections defining tokens, which are operators and keywords
+    pub fn entries(&self) -> impl Iterator<Item = &(String, String)> {
+        return self
+            .operators
+            .0
+            .iter()
+            .chain(self.keywords.0.iter())
+            .chain(self.contextual_keywords.0.iter());
+    }
+
+    /// Fails on duplicated constants, token values or generated variant names, and on malformed delimiters
+    pub fn validate(&self) -> Result<(), String> {
+        let mut constants = HashMap::new();
+        let mut values = HashMap::new();
+        let mut variants = HashMap::new();
+        for (constant, value) in self.entries() {
+            if let Some(previous) = constants.insert(constant, value) {
+                return Err(format!(
+                    "duplicate constant {} for \"{}\" and \"{}\"",
+                    constant, previous, value
+                ));
+            }
+            if let Some(previous) = values.insert(value, constant) {
+                return Err(format!(
+                    "duplicate value \"{}\" for {} and {}",
+                    value, previous, constant
+                ));
+            }
+            if let Some(previous) = variants.insert(variant_name(constant), constant) {
+                return Err(format!(
+                    "{} and {} generate the same variant {}",
+                    previous,
+                    constant,
+                    variant_name(constant)
+                ));
+            }
+            if value.is_empty() || value.chars().any(char::is_whitespace) {
+                return Err(format!("invalid value \"{}\" for {}", value, constant));
+            }
+        }
+
+        let delimiters = [
+            (&self.patterns, 1),
+            (&self.line_comments, 1),
+            (&self.doc_comments, 1),
+            (&self.block_comments, 2),
+            (&self.strings, 1),
+        ];
+        for (section, parts) in delimiters {
+            for (constant, value) in &section.0 {
+                if let Some(previous) = constants.insert(constant, value) {
+                    return Err(format!(
+                        "duplicate constant {} for \"{}\" and \"{}\"",
+                        constant, previous, value
+                    ));
+                }
+                if value.split_whitespace().count() != parts {
+                    return Err(format!("invalid value \"{}\" for {}", value, constant));
+                }
+            }
+        }
+        for (constant, value) in &self.strings.0 {
+            if value.chars().count() != 1 {
+                return Err(format!("invalid quote \"{}\" for {}", value, constant));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Variant names of the constants which predate the generated tokens, whose names are kept as token.yaml is
+/// shared with the VM and editor tooling
+const VARIANT_NAMES: [(&str, &str); 6] = [
+    ("OP_ADD", "Plus"),
+    ("OP_NEG", "Minus"),
+    ("OP_MUL", "Asterisk"),
+    ("OP_DIV", "ForwardSlash"),
+    ("KW_COMP", "Component"),
+    ("KW_SYS", "System"),
+];
+
+/// Convert a constant name such as `OP_PLUS_EQUAL` into a variant name such as `PlusEqual`
+pub fn variant_name(constant: &str) -> String {
+    if let Some((_, name)) = VARIANT_NAMES.iter().find(|(c, _)| *c == constant) {
+        return name.to_string();
+    }
+    let name = constant
+        .strip_prefix("OP_")
+        .or_else(|| constant.strip_prefix("KW_"))
+        .unwrap_or(constant);
+    return name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let lower = part.to_lowercase();
+            let mut chars = lower.chars();
+            return match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            };
+        })
+        .collect();
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SectionVisitor;
+
+        impl<'de> Visitor<'de> for SectionVisitor {
+            type Value = Section;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                return write!(f, "a map of constant names to token values");
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Section, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                return Ok(Section(entries));
+            }
+        }
+
+        return deserializer.deserialize_map(SectionVisitor);
+    }
+}
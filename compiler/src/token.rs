@@ -1,211 +1,521 @@
-use std::{
-    collections::{HashMap, HashSet},
-    iter::Peekable,
-    mem,
-    str::Chars,
-};
-
-use regex::Regex;
-
-#[derive(Debug, Clone)]
-pub enum TokenType {
-    Colon,
-    Semicolon,
-    Dot,
-    Plus,
-    Minus,
-    Equal,
-    Asterisk,
-    Lambda,
-    ForwardSlash,
-    ParanthesisOpen,
-    ParanthesisClose,
-    If,
-    Continue,
-    For,
-    Loop,
-    While,
-    Else,
-    Return,
-    Break,
-    Match,
-    True,
-    False,
-    Const,
-    Function,
-    Event,
-    Emit,
-    System,
-    Use,
-    Identifier(String),
-    LiteralString(String),
-    Comment(String),
-    Indentation(usize),
-}
-
-#[derive(Debug)]
+use std::collections::HashMap;
+use std::path::Path;
+use std::{fmt, fs, iter::Peekable, str::Chars, sync::OnceLock};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::dictionary::{self, Record};
+use crate::scanner::Lexeme;
+use crate::spec::Spec;
+
+// keyword and operator variants, along with the `OPERATORS` and `KEYWORDS` tables, are generated from token.yaml
+include!(concat!(env!("OUT_DIR"), "/tokens.rs"));
+
+define_token_type! {
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum TokenType {
+        Identifier(String),
+        LiteralInteger(Integer),
+        LiteralFloat(Float),
+        LiteralDuration(u64),
+        LiteralString(String),
+        LiteralChar(char),
+        LiteralTemplate(Vec<TemplateSegment>),
+        /// `#RRGGBB` or `#RRGGBBAA` colour
+        LiteralColor(Color),
+        /// `(` opening a matrix literal such as `(1 2 3; 4 5 6)`, whose elements are separated by whitespace
+        /// and rows by `;`
+        MatrixOpen,
+        /// `v(` opening a column vector literal such as `v(1f 2f 3f)`
+        VectorOpen,
+        Comment(String),
+        /// `##` or `///` comment, taken by the parser as the documentation of the following declaration
+        DocComment(String),
+        /// Whitespace which is not significant, only produced in lossless mode
+        Whitespace(String),
+        /// Source text which could not be scanned, only produced in recovery mode
+        Error(String),
+        /// Token registered by the host engine, see `Dictionary::add_custom`. Its text is the source of its span
+        Custom(u32),
+        Newline,
+        Indent,
+        Dedent,
+    }
+}
+
+impl TokenType {
+    /// Returns true if the token could be the last token of an operand, in which case a following `-` is
+    /// a binary operator instead of the sign of a numeric literal. `>` may close a component access such as
+    /// `entity<Health>`, while a `-` following a comparison is parsed as a negation instead
+    pub fn ends_operand(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::Identifier(_)
+                | TokenType::LiteralInteger(_)
+                | TokenType::LiteralFloat(_)
+                | TokenType::LiteralDuration(_)
+                | TokenType::LiteralString(_)
+                | TokenType::LiteralChar(_)
+                | TokenType::LiteralTemplate(_)
+                | TokenType::LiteralColor(_)
+                | TokenType::ParanthesisClose
+                | TokenType::BracketClose
+                | TokenType::BraceClose
+                | TokenType::Greater
+                | TokenType::True
+                | TokenType::False
+                | TokenType::SelfRef
+        ) || self.contextual_keyword().is_some();
+    }
+
+    /// Returns true for whitespace and comments, which do not affect the indentation of the line they are on.
+    /// Doc comments are trivia as well, although the parser attaches them to the following declaration
+    pub fn is_trivia(&self) -> bool {
+        return matches!(
+            self,
+            TokenType::Comment(_) | TokenType::DocComment(_) | TokenType::Whitespace(_)
+        );
+    }
+
+    /// Returns the name of the identifier, contextual keywords such as `on`, `to` or `as` are resolved as
+    /// identifiers outside of the clauses they are keywords of
+    pub fn identifier(&self) -> Option<&str> {
+        return match self {
+            TokenType::Identifier(name) => Some(name),
+            _ => self.contextual_keyword(),
+        };
+    }
+}
+
+/// Kind of comment started by a comment opener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    /// `#` or `//` until the end of the line
+    Line,
+    /// `##` or `///` until the end of the line
+    Doc,
+    /// `/* ... */`, or custom delimiters, which may be nested and span multiple lines
+    Block,
+}
+
+/// Segment of an interpolated string literal such as `'{event.sender} says {event.message}'`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateSegment {
+    Text(String),
+    /// Tokens of the expression within a `{...}` placeholder, empty for positional `{}` placeholders
+    Placeholder(Vec<Token>),
+}
+
+/// Scalar types which could be inferred from a hard-coded value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    I32,
+    I64,
+    U32,
+    U64,
+    F32,
+    F64,
+    Duration,
+}
+
+impl fmt::Display for ScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ScalarType::I32 => "number",
+            ScalarType::I64 => "i64",
+            ScalarType::U32 => "u32",
+            ScalarType::U64 => "u64",
+            ScalarType::F32 => "fractional",
+            ScalarType::F64 => "f64",
+            ScalarType::Duration => "duration",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Value of an integer literal, typed by its suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integer {
+    I32(i32),
+    I64(i64),
+    U32(u32),
+    U64(u64),
+}
+
+impl Integer {
+    pub fn scalar_type(&self) -> ScalarType {
+        return match self {
+            Integer::I32(_) => ScalarType::I32,
+            Integer::I64(_) => ScalarType::I64,
+            Integer::U32(_) => ScalarType::U32,
+            Integer::U64(_) => ScalarType::U64,
+        };
+    }
+}
+
+/// Value of a floating point literal, typed by its suffix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Float {
+    F32(f32),
+    F64(f64),
+}
+
+impl Float {
+    pub fn scalar_type(&self) -> ScalarType {
+        return match self {
+            Float::F32(_) => ScalarType::F32,
+            Float::F64(_) => ScalarType::F64,
+        };
+    }
+}
+
+/// Value of a colour literal, the alpha channel of a `#RRGGBB` colour is opaque
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     kind: TokenType,
+    span: Span,
     pos: Position,
 }
 
 impl Token {
-    pub fn new(kind: TokenType, line: u32, col: u32) -> Token {
-        return Token {
-            kind,
-            pos: Position { line, col },
+    pub fn new(kind: TokenType, span: Span, pos: Position) -> Token {
+        return Token { kind, span, pos };
+    }
+
+    pub fn kind(&self) -> &TokenType {
+        return &self.kind;
+    }
+
+    /// Bytes of the source file covered by the token
+    pub fn span(&self) -> Span {
+        return self.span;
+    }
+
+    /// Line and column of the start of the token, see `SourceMap` for the columns used by editors
+    pub fn pos(&self) -> Position {
+        return self.pos;
+    }
+
+    pub fn file(&self) -> FileId {
+        return self.span.file;
+    }
+
+    /// Returns the token moved by a number of bytes and lines, along with the tokens of its placeholders
+    pub(crate) fn shifted(&self, bytes: isize, lines: i64) -> Token {
+        let kind = match &self.kind {
+            TokenType::LiteralTemplate(segments) => {
+                let segments = segments
+                    .iter()
+                    .map(|segment| match segment {
+                        TemplateSegment::Placeholder(tokens) => TemplateSegment::Placeholder(
+                            tokens.iter().map(|t| t.shifted(bytes, lines)).collect(),
+                        ),
+                        text => text.clone(),
+                    })
+                    .collect();
+                TokenType::LiteralTemplate(segments)
+            }
+            kind => kind.clone(),
+        };
+        let span = Span {
+            file: self.span.file,
+            start: self.span.start.checked_add_signed(bytes).unwrap(),
+            end: self.span.end.checked_add_signed(bytes).unwrap(),
         };
+        let pos = Position {
+            line: (self.pos.line as i64 + lines) as u32,
+            col: self.pos.col,
+        };
+        return Token::new(kind, span, pos);
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     pub line: u32,
     pub col: u32,
 }
 
-pub struct RegexMap<T> {
-    patterns: Vec<String>,
-    regexes: Vec<Regex>,
-    values: Vec<T>,
+/// Byte offsets of a region of a source file, from its start up to but excluding its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
 }
 
-impl<T> RegexMap<T> {
-    pub fn new() -> RegexMap<T> {
-        return RegexMap {
-            regexes: Vec::new(),
-            values: Vec::new(),
-            patterns: Vec::new(),
-        };
+impl Span {
+    pub fn len(&self) -> usize {
+        return self.end - self.start;
     }
 
-    pub fn insert(&mut self, pattern: String, value: T) -> Option<T> {
-        for (i, p) in self.patterns.iter().enumerate() {
-            if p == &pattern {
-                return Some(mem::replace(&mut self.values[i], value));
-            }
-        }
-        self.values.push(value);
-        self.regexes.push(Regex::new(&pattern).unwrap());
-        self.patterns.push(pattern);
-        return None;
+    pub fn is_empty(&self) -> bool {
+        return self.start == self.end;
     }
 
-    pub fn get(&self, value: &String) -> Option<&T> {
-        for (i, r) in self.regexes.iter().enumerate() {
-            if r.is_match(&value) {
-                return Some(&self.values[i]);
-            }
-        }
-        return None;
+    /// Returns the span from the start of this span up to the end of another span of the same file
+    pub fn to(&self, end: Span) -> Span {
+        return Span {
+            file: self.file,
+            start: self.start,
+            end: end.end.max(self.start),
+        };
     }
 }
 
+/// Identifier of a source file scanned by a lexer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+/// Priority of the records of the keywords and operators of token.yaml, which win over identifiers
+pub const KEYWORD_PRIORITY: i32 = 1;
+/// Priority of the identifier pattern
+pub const IDENTIFIER_PRIORITY: i32 = 0;
+
 pub struct Dictionary {
-    patterns: RegexMap<fn(&String) -> TokenType>,
-    exact: HashMap<String, TokenType>,
-    breakers: HashSet<char>,
+    records: dictionary::Dictionary<Lexeme>,
+    /// Openers of the line and doc comments registered by the host
+    line_comments: Vec<(String, CommentKind)>,
+    /// Opener and closer of block comments
+    block_comments: Vec<(String, String)>,
+    /// Quotes of string and char literals
+    quotes: Vec<char>,
+    /// Id of the next custom token of an extension
+    next_custom: u32,
+}
+
+/// Error loading an extension of a dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionError {
+    Io(String),
+    /// Source which is not in the format of token.yaml, or which has duplicated or malformed entries
+    Invalid(String),
+    Record(dictionary::Error),
+    /// Custom token ids past `u32::MAX`
+    Exhausted,
+}
+
+impl fmt::Display for ExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtensionError::Io(error) => write!(f, "failed to read extension: {}", error),
+            ExtensionError::Invalid(error) => write!(f, "invalid extension: {}", error),
+            ExtensionError::Record(error) => write!(f, "{}", error),
+            ExtensionError::Exhausted => write!(f, "no custom token id is left"),
+        }
+    }
 }
 
 impl Dictionary {
     pub fn new() -> Dictionary {
-        let mut d = Dictionary {
-            patterns: RegexMap::new(),
-            exact: HashMap::new(),
-            breakers: HashSet::new(),
+        return Dictionary {
+            records: Dictionary::default_records().clone(),
+            line_comments: Vec::new(),
+            block_comments: vec![("/*".to_string(), "*/".to_string())],
+            quotes: vec!['"', '\''],
+            next_custom: 0,
         };
+    }
+
+    /// Records of the tokens of token.yaml and of the identifier pattern, which are only compiled once per process
+    fn default_records() -> &'static dictionary::Dictionary<Lexeme> {
+        static RECORDS: OnceLock<dictionary::Dictionary<Lexeme>> = OnceLock::new();
+        return RECORDS.get_or_init(|| {
+            let mut records = dictionary::Dictionary::new();
+            // the tables of token.yaml are free of duplicates, which is checked by build.rs
+            for (text, kind) in OPERATORS
+                .into_iter()
+                .chain(KEYWORDS)
+                .chain(CONTEXTUAL_KEYWORDS)
+            {
+                let record = Record::Exact(KEYWORD_PRIORITY, text.to_string());
+                records.add_record(record, Lexeme::Token(kind)).unwrap();
+            }
+            // identifiers follow UAX#31, with a leading `_` allowed as well
+            let record = Record::Pattern(
+                IDENTIFIER_PRIORITY,
+                r"[\p{XID_Start}_]\p{XID_Continue}*".to_string(),
+            );
+            records.add_record(record, Lexeme::Identifier).unwrap();
+            return records;
+        });
+    }
+
+    /// Register a record resolving to a lexeme, failing if it is ambiguous with an existing record of the same
+    /// priority. For example an engine could register `Record::Exact(2, "fn")` as an alias of `function`
+    pub fn add_record(&mut self, record: Record, lexeme: Lexeme) -> Result<(), dictionary::Error> {
+        return self.records.add_record(record, lexeme);
+    }
 
-        d.breakers.insert(' ');
-        d.breakers.insert('\n');
-        d.breakers.insert('\r');
-        d.breakers.insert('\t');
-        d.breakers.insert('(');
-        d.breakers.insert(')');
-        d.breakers.insert(':');
-        d.breakers.insert(';');
-        d.breakers.insert('+');
-        d.breakers.insert('-');
-        d.breakers.insert('*');
-        d.breakers.insert('/');
-        d.breakers.insert('#');
-        d.breakers.insert('.');
-        d.exact.insert(":".to_string(), TokenType::Colon);
-        d.exact.insert(";".to_string(), TokenType::Semicolon);
-        d.exact.insert(".".to_string(), TokenType::Dot);
-        d.exact.insert("+".to_string(), TokenType::Plus);
-        d.exact.insert("-".to_string(), TokenType::Minus);
-        d.exact.insert("*".to_string(), TokenType::Asterisk);
-        d.exact.insert("=".to_string(), TokenType::Equal);
-        d.exact.insert("=>".to_string(), TokenType::Lambda);
-        d.exact.insert("(".to_string(), TokenType::ParanthesisOpen);
-        d.exact.insert(")".to_string(), TokenType::ParanthesisClose);
-        d.exact.insert("/".to_string(), TokenType::ForwardSlash);
-        d.exact.insert("if".to_string(), TokenType::If);
-        d.exact.insert("continue".to_string(), TokenType::Continue);
-        d.exact.insert("for".to_string(), TokenType::For);
-        d.exact.insert("loop".to_string(), TokenType::Loop);
-        d.exact.insert("while".to_string(), TokenType::While);
-        d.exact.insert("else".to_string(), TokenType::Else);
-        d.exact.insert("return".to_string(), TokenType::Return);
-        d.exact.insert("break".to_string(), TokenType::Break);
-        d.exact.insert("match".to_string(), TokenType::Match);
-        d.exact.insert("true".to_string(), TokenType::True);
-        d.exact.insert("false".to_string(), TokenType::False);
-        d.exact.insert("const".to_string(), TokenType::Const);
-        d.exact.insert("function".to_string(), TokenType::Function);
-        d.exact.insert("event".to_string(), TokenType::Event);
-        d.exact.insert("emit".to_string(), TokenType::Emit);
-        d.exact.insert("system".to_string(), TokenType::System);
-        d.exact.insert("use".to_string(), TokenType::Use);
-        d.patterns
-            .insert(r"^[a-zA-Z][_a-zA-Z0-9]{0, 30}$".to_string(), |value| {
-                TokenType::Identifier(value.to_string())
-            });
-
-        return d;
-    }
-
-    pub fn get(&self, index: &String) -> Option<TokenType> {
-        match self.get_exact(index) {
-            Some(v) => Some(v),
-            None => self.get_pattern(index),
+    /// Register a record resolving to `TokenType::Custom(id)`, with the same conflicts as `add_record`. The id
+    /// `u32::MAX` is reserved, as no id of an extension could follow it
+    pub fn add_custom(&mut self, record: Record, id: u32) -> Result<(), ExtensionError> {
+        let next_custom = id.checked_add(1).ok_or(ExtensionError::Exhausted)?;
+        self.records
+            .add_record(record, Lexeme::Token(TokenType::Custom(id)))
+            .map_err(ExtensionError::Record)?;
+        self.next_custom = self.next_custom.max(next_custom);
+        return Ok(());
+    }
+
+    /// Register the opener of a line or doc comment, which wins over the default openers
+    pub fn add_line_comment(&mut self, opener: &str, kind: CommentKind) {
+        assert!(kind != CommentKind::Block, "block comments need a closer");
+        self.line_comments.push((opener.to_string(), kind));
+    }
+
+    /// Register the delimiters of a block comment, which may be nested unless they are the same
+    pub fn add_block_comment(&mut self, opener: &str, closer: &str) {
+        self.block_comments
+            .push((opener.to_string(), closer.to_string()));
+    }
+
+    /// Register the quote of a string literal, which is never a char literal
+    pub fn add_string_delimiter(&mut self, quote: char) {
+        self.quotes.push(quote);
+    }
+
+    /// Add the tokens of an extension in the format of token.yaml, returning the id of the custom token of each
+    /// constant. Operators, keywords and patterns win over identifiers and may not be ambiguous with existing
+    /// tokens, delimiters are given by the `line_comments`, `doc_comments`, `block_comments` and `strings`
+    /// sections. Nothing is added if the extension is invalid
+    pub fn add_extension(&mut self, source: &str) -> Result<HashMap<String, u32>, ExtensionError> {
+        let spec: Spec =
+            serde_yaml::from_str(source).map_err(|e| ExtensionError::Invalid(e.to_string()))?;
+        spec.validate().map_err(ExtensionError::Invalid)?;
+        if let Some((constant, _)) = spec.contextual_keywords.0.first() {
+            let error = format!("contextual keyword {} can not be extended", constant);
+            return Err(ExtensionError::Invalid(error));
+        }
+
+        let count = spec.operators.0.len() + spec.keywords.0.len() + spec.patterns.0.len();
+        self.next_custom
+            .checked_add(count as u32)
+            .ok_or(ExtensionError::Exhausted)?;
+
+        let mut records = self.records.clone();
+        let mut ids = HashMap::new();
+        let exact = spec.operators.0.iter().chain(&spec.keywords.0);
+        let exact =
+            exact.map(|(constant, text)| (constant, Record::Exact(KEYWORD_PRIORITY, text.clone())));
+        let patterns = spec.patterns.0.iter();
+        let patterns = patterns.map(|(constant, pattern)| {
+            (constant, Record::Pattern(KEYWORD_PRIORITY, pattern.clone()))
+        });
+        for (constant, record) in exact.chain(patterns) {
+            let id = self.next_custom + ids.len() as u32;
+            records
+                .add_record(record, Lexeme::Token(TokenType::Custom(id)))
+                .map_err(ExtensionError::Record)?;
+            ids.insert(constant.clone(), id);
+        }
+        self.records = records;
+        self.next_custom += ids.len() as u32;
+
+        for (_, opener) in &spec.line_comments.0 {
+            self.add_line_comment(opener, CommentKind::Line);
+        }
+        for (_, opener) in &spec.doc_comments.0 {
+            self.add_line_comment(opener, CommentKind::Doc);
+        }
+        for (_, delimiters) in &spec.block_comments.0 {
+            let mut delimiters = delimiters.split_whitespace();
+            self.add_block_comment(delimiters.next().unwrap(), delimiters.next().unwrap());
         }
+        for (_, quote) in &spec.strings.0 {
+            self.add_string_delimiter(quote.chars().next().unwrap());
+        }
+        return Ok(ids);
     }
 
-    pub fn get_exact(&self, index: &String) -> Option<TokenType> {
-        return match self.exact.get(index) {
-            Some(v) => Some(v.clone()),
-            None => None,
-        };
+    /// Add the tokens of an extension file, see `add_extension`
+    pub fn load_extension(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<HashMap<String, u32>, ExtensionError> {
+        let source = fs::read_to_string(path).map_err(|e| ExtensionError::Io(e.to_string()))?;
+        return self.add_extension(&source);
+    }
+
+    pub fn records(&self) -> &dictionary::Dictionary<Lexeme> {
+        return &self.records;
     }
 
-    pub fn get_pattern(&self, index: &String) -> Option<TokenType> {
-        return match self.patterns.get(index) {
-            Some(v) => Some(v(index)),
-            None => None,
+    /// Resolve a word into a keyword or identifier, words are normalised to NFC so identifiers which only
+    /// differ by their encoding are the same
+    pub fn get(&self, index: &str) -> Option<TokenType> {
+        let index: String = index.nfc().collect();
+        return match self.records.get(&index)? {
+            Lexeme::Token(kind) => Some(kind.clone()),
+            Lexeme::Identifier => Some(TokenType::Identifier(index)),
+            Lexeme::Number => None,
         };
     }
 
-    pub fn is_breaker(&self, c: &char) -> bool {
-        // return self.breakers.contains(c);
-        return !c.is_alphanumeric();
+    /// Returns the kind of the comment opened at `c` along with the number of characters of its opener. The
+    /// longest registered opener wins, otherwise a third `#` or `/` makes a doc comment opener a regular line
+    /// comment, e.g. `####` or `////` separators
+    pub fn comment_opener(
+        &self,
+        c: &char,
+        chars: &Peekable<Chars>,
+    ) -> Option<(CommentKind, usize)> {
+        let blocks = self.block_comments.iter();
+        let registered = self
+            .line_comments
+            .iter()
+            .map(|(opener, kind)| (opener, *kind));
+        let registered = registered.chain(blocks.map(|(opener, _)| (opener, CommentKind::Block)));
+        let longest = registered
+            .filter(|(opener, _)| starts_with(*c, chars, opener))
+            .max_by_key(|(opener, _)| opener.chars().count());
+        if let Some((opener, kind)) = longest {
+            return Some((kind, opener.chars().count()));
+        }
+
+        let mut ahead = chars.clone();
+        let mut next = || ahead.next();
+        return match (c, next(), next(), next()) {
+            ('#', Some('#'), Some('#'), _) => Some((CommentKind::Line, 1)),
+            ('#', Some('#'), _, _) => Some((CommentKind::Doc, 2)),
+            ('#', _, _, _) => Some((CommentKind::Line, 1)),
+            ('/', Some('/'), Some('/'), Some('/')) => Some((CommentKind::Line, 2)),
+            ('/', Some('/'), Some('/'), _) => Some((CommentKind::Doc, 3)),
+            ('/', Some('/'), _, _) => Some((CommentKind::Line, 2)),
+            _ => None,
+        };
     }
 
-    pub fn is_comment_opener(&self, c: &char) -> bool {
-        return *c == '#';
+    /// Returns the opener and closer of the block comment opened at `c`, the longest opener wins
+    pub fn block_comment(&self, c: &char, chars: &Peekable<Chars>) -> Option<(&str, &str)> {
+        let (opener, closer) = self
+            .block_comments
+            .iter()
+            .filter(|(opener, _)| starts_with(*c, chars, opener))
+            .max_by_key(|(opener, _)| opener.chars().count())?;
+        return Some((opener, closer));
     }
 
     pub fn is_string_literal_opener(&self, c: &char) -> bool {
-        return *c == '\'';
+        return self.quotes.contains(c);
     }
 
-    pub fn consume_indentation(&self, chars: &mut Peekable<Chars>) -> usize {
-        let mut indentation: usize = 0;
+    /// Consume the leading tabs and spaces of a line
+    pub fn consume_indentation(&self, chars: &mut Peekable<Chars>) -> String {
+        let mut indentation = String::new();
         while let Some(c) = chars.peek() {
-            if *c != '\t' {
+            if *c != '\t' && *c != ' ' {
                 break;
             }
-            indentation += 1;
+            indentation.push(*c);
             chars.next();
         }
         return indentation;
@@ -215,3 +525,9 @@ impl Dictionary {
         return c.is_whitespace();
     }
 }
+
+/// Returns true if the already consumed `c` followed by the given characters starts with `text`
+pub(crate) fn starts_with(c: char, chars: &Peekable<Chars>, text: &str) -> bool {
+    let mut input = [c].into_iter().chain(chars.clone());
+    return text.chars().all(|t| input.next() == Some(t));
+}